@@ -1,4 +1,7 @@
-use crate::{chats_view::*, input_view::*, messages_view::*, state::*, utilities::*, *};
+use crate::{
+	attachments_view::*, chat_picker::*, chats_view::*, completion::Completion, input_view::*,
+	messages_view::*, names::NameCache, paste::Paste, state::*, utilities::*, *,
+};
 use core::time::Duration;
 use crossterm::event::{poll, read, Event, KeyCode, KeyModifiers};
 use sdk::commands::APICommand;
@@ -7,15 +10,21 @@ use std::{
 	cmp::{max, min},
 	io::{Error, Stdout},
 	mem::take,
+	path::PathBuf,
 };
 use tokio::sync::RwLock;
 use tui::{
-	layout::{Constraint, Direction, Layout},
+	layout::{Alignment, Constraint, Direction, Layout, Rect},
 	style::Style,
+	terminal::Frame,
 	text::{Span, Spans},
-	widgets::{Block, BorderType, Borders, Paragraph, Wrap},
+	widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
 };
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// the tapbacks that can be sent, in the order that the api numbers them
+pub const TAPBACKS: [&str; 6] = ["love", "like", "dislike", "laugh", "emphasize", "question"];
 
 pub struct MainApp {
 	// index of currently selected conversation in the chats array
@@ -29,9 +38,16 @@ pub struct MainApp {
 	redraw_all: bool,
 	// how far the help display is scrolled down
 	help_scroll: u16,
+	// the destructive action that is waiting on the user to confirm it
+	confirmation: Option<Confirmation>,
+	// the popup for finding a chat by its name, if it's showing
+	picker: Option<ChatPicker>,
+	// the names and addresses of everyone in the current chat, if they're showing
+	members: Option<Vec<(String, String)>>,
 	client: Arc<RwLock<sdk::APIClient>>,
 	chats_view: ChatsView, // the different views
 	msgs_view: MessagesView,
+	attachments_view: AttachmentsView,
 	input_view: InputView,
 	address_view: InputView,
 	compose_body_view: InputView,
@@ -40,12 +56,14 @@ pub struct MainApp {
 
 impl MainApp {
 	pub async fn new(set: Settings) -> anyhow::Result<MainApp> {
+		let mut input_view = InputView::new();
 		let mut address_view = InputView::new();
 		let mut compose_body_view = InputView::new();
 		let mut config = sdk::SDKConfig::default();
 
 		address_view.custom_title = Some(set.to_title.to_owned());
 		compose_body_view.custom_title = Some(set.compose_title.to_owned());
+		input_view.last_commands = InputView::load_history(set.history_size);
 
 		if let Some(ref url) = set.remote_url {
 			if !set.secure {
@@ -129,17 +147,23 @@ impl MainApp {
 
 		let msgs_view = MessagesView::new(client_arc.clone());
 
+		let attachments_view = AttachmentsView::new(client_arc.clone());
+
 		Ok(MainApp {
 			selected_chat: None,
 			selected_box: DisplayBox::Chats,
 			quit_app: false,
 			redraw_all: false,
 			help_scroll: 0,
-			input_view: InputView::new(),
+			confirmation: None,
+			picker: None,
+			members: None,
+			input_view,
 			client: client_arc,
 			settings: set,
 			chats_view,
 			msgs_view,
+			attachments_view,
 			address_view,
 			compose_body_view,
 		})
@@ -240,11 +264,51 @@ impl MainApp {
 			}
 		}
 
+		// so that they don't lose anything they were in the middle of writing
+		self.stash_draft();
+
+		// kitty images don't go away when we exit, so take them off the screen
+		self.msgs_view.write_images(term.backend_mut(), false)?;
+
 		// make the terminal echo everything input again
 		Ok(crossterm::terminal::disable_raw_mode()?)
 	}
 
 	pub fn draw(&mut self, term: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<(), Error> {
+		self.draw_frame(term)?;
+
+		let show_overlays = self.selected_box != DisplayBox::Help
+			&& self.selected_box != DisplayBox::Attachments
+			&& self.confirmation.is_none()
+			&& self.picker.is_none()
+			&& self.members.is_none()
+			&& self.address_view.completion.is_none()
+			&& self.input_view.completion.is_none();
+
+		// some graphics protocols leave images on the screen until something
+		// is drawn over them, and tui doesn't know they're there. So if they've
+		// moved, we have to clear the screen and draw everything again.
+		if self.msgs_view.has_stale_images(show_overlays) {
+			term.clear()?;
+			self.msgs_view.drawn_images.clear();
+			self.draw_frame(term)?;
+		}
+
+		// go back over the links that were just drawn and make them clickable.
+		// This has to be done after tui draws, since it can't draw them itself
+		if self.settings.hyperlinks && show_overlays {
+			self.msgs_view
+				.write_hyperlinks(term.backend_mut(), &self.settings)?;
+		}
+
+		// and the same goes for images
+		self.msgs_view
+			.write_images(term.backend_mut(), show_overlays)?;
+
+		Ok(())
+	}
+
+	fn draw_frame(&mut self, term: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<(), Error> {
 		// gotta make sure we can actually access the settings
 		let colorscheme = &self.settings.colorscheme;
 
@@ -272,18 +336,39 @@ impl MainApp {
 					.scroll((self.help_scroll, 0));
 
 				f.render_widget(help_msg_widget, size);
+			} else if let DisplayBox::Attachments = self.selected_box {
+				// the attachments browser also takes up the whole screen,
+				// except for the hint line, so that you can see how downloads go
+				let layout = Layout::default()
+					.direction(Direction::Vertical)
+					.constraints([Constraint::Min(3), Constraint::Length(1)].as_ref())
+					.split(size);
+
+				self.attachments_view
+					.draw_view(f, layout[0], &self.settings);
+
+				let hint_msg = read_state!().hint_msg.to_owned();
+
+				let hint_widget = Paragraph::new(vec![Spans::from(vec![Span::styled(
+					hint_msg,
+					Style::default().fg(colorscheme.hints_box),
+				)])]);
+				f.render_widget(hint_widget, layout[1]);
 			} else {
 				// we have to get this string first so that we know how long it is
 				// to make it left aligned
 				let battery_msg = read_state!().battery_string();
 
+				// the input box grows as they type more lines
+				let input_height = self.input_view.height(size.width, &self.settings);
+
 				// set up layouts
 				let main_layout = Layout::default()
 					.direction(Direction::Vertical)
 					.constraints(
 						[
 							Constraint::Min(5),
-							Constraint::Length(3),
+							Constraint::Length(input_height),
 							Constraint::Length(1),
 						]
 						.as_ref(),
@@ -322,6 +407,10 @@ impl MainApp {
 				if DisplayBox::ComposeAddress == self.selected_box
 					|| DisplayBox::ComposeBody == self.selected_box
 				{
+					let body_height = self
+						.compose_body_view
+						.height(content_layout[1].width, &self.settings);
+
 					// set up a new layout
 					let message_layout = Layout::default()
 						.direction(Direction::Vertical)
@@ -329,7 +418,7 @@ impl MainApp {
 							[
 								Constraint::Length(3),
 								Constraint::Min(3),
-								Constraint::Length(3),
+								Constraint::Length(body_height),
 							]
 							.as_ref(),
 						)
@@ -357,12 +446,21 @@ impl MainApp {
 						!address_cursor,
 						&self.settings,
 					);
+
+					if let Some(ref comp) = self.address_view.completion {
+						comp.draw_popup(f, message_layout[0], &self.settings);
+					}
 				} else {
 					// if it's not, just draw the messages view like normal
 					self.msgs_view
 						.draw_view(f, content_layout[1], !chats_selected, &self.settings);
 				}
 
+				// show what tab could complete to, over everything else
+				if let Some(ref comp) = self.input_view.completion {
+					comp.draw_popup(f, main_layout[1], &self.settings);
+				}
+
 				// create a span for the help box add the help string
 				let hint_msg = read_state!().hint_msg.to_owned();
 
@@ -384,6 +482,48 @@ impl MainApp {
 				let batt_widget = Paragraph::new(batt_span);
 				f.render_widget(batt_widget, bottom_layout[1]);
 			}
+
+			// they're picking a chat, so show that over the rest of the app
+			if let Some(ref picker) = self.picker {
+				picker.draw_view(f, size, &self.chats_view.chats, &self.settings);
+			}
+
+			if let Some(ref members) = self.members {
+				MainApp::draw_members(f, members, size, &self.settings);
+			}
+
+			// and if we're waiting on the user to confirm something,
+			// draw the prompt over everything else
+			if let Some(ref conf) = self.confirmation {
+				let width = min(max(conf.prompt.len() as u16 + 4, 30), size.width);
+				let popup = Utilities::centered_rect(width, 5, size);
+
+				let text = vec![
+					Spans::from(vec![Span::styled(
+						conf.prompt.as_str(),
+						Style::default().fg(colorscheme.text_color),
+					)]),
+					Spans::from(vec![Span::raw("")]),
+					Spans::from(vec![Span::styled(
+						"(y/N)",
+						Style::default().fg(colorscheme.hints_box),
+					)]),
+				];
+
+				let confirm_widget = Paragraph::new(text)
+					.block(
+						Block::default()
+							.title("| confirm |")
+							.borders(Borders::ALL)
+							.border_type(BorderType::Rounded)
+							.border_style(Style::default().fg(colorscheme.selected_box)),
+					)
+					.alignment(Alignment::Center)
+					.wrap(Wrap { trim: true });
+
+				f.render_widget(Clear, popup);
+				f.render_widget(confirm_widget, popup);
+			}
 		})?;
 
 		Ok(())
@@ -402,6 +542,7 @@ impl MainApp {
 				Err(_) => (0, 0),
 			}
 		};
+		let last_hint = read_state!().hint_msg.to_owned();
 
 		loop {
 			if !poll(Duration::from_millis(20)).unwrap() {
@@ -473,6 +614,34 @@ impl MainApp {
 					break;
 				}
 
+				let has_previews = !read_state!().new_previews.is_empty();
+
+				if has_previews {
+					let previews = if let Ok(mut state) = STATE.write() {
+						take(&mut state.new_previews)
+					} else {
+						Vec::new()
+					};
+
+					self.msgs_view.add_previews(previews);
+
+					break;
+				}
+
+				let has_thumbnails = !read_state!().new_thumbnails.is_empty();
+
+				if has_thumbnails {
+					let thumbnails = if let Ok(mut state) = STATE.write() {
+						take(&mut state.new_thumbnails)
+					} else {
+						Vec::new()
+					};
+
+					self.msgs_view.add_thumbnails(thumbnails);
+
+					break;
+				}
+
 				// we check at every poll interval to see if the terminal has
 				// resized. If it has, we break from getting input so that it can
 				// redraw with the new size.
@@ -495,12 +664,123 @@ impl MainApp {
 				if disc {
 					break;
 				};
+
+				// and if something in the background (like a download) has
+				// changed the hint, so that they can see how it's going
+				let hint_changed = read_state!().hint_msg != last_hint;
+
+				if hint_changed {
+					break;
+				}
 			} else {
 				let (code, modifiers) = match read()? {
 					Event::Key(event) => (event.code, event.modifiers),
 					_ => continue,
 				};
 
+				// if there's a prompt up, the next key either confirms it or
+				// cancels it, and nothing else gets to handle the key
+				if let Some(conf) = take(&mut self.confirmation) {
+					match code {
+						KeyCode::Char('y') | KeyCode::Char('Y') => {
+							self.run_action(conf.action).await
+						}
+						_ => {
+							conf.action.cancel();
+							hint!("cancelled");
+						}
+					}
+					break;
+				}
+
+				// the members popup just goes away with any key
+				if self.members.take().is_some() {
+					break;
+				}
+
+				// and the same goes for the chat picker
+				if let Some(ref mut picker) = self.picker {
+					match picker.route_key(code, modifiers, &self.chats_view.chats) {
+						PickerAction::Open(idx) => {
+							self.picker = None;
+							self.load_in_conversation(idx).await;
+						}
+						PickerAction::Close => self.picker = None,
+						PickerAction::Nothing => (),
+					}
+					break;
+				}
+
+				// any key but tab stops completing, and enter just keeps the
+				// candidate instead of running the command right away
+				if code != KeyCode::Tab && code != KeyCode::BackTab {
+					let completing = self.input_view.completion.take().is_some()
+						| self.address_view.completion.take().is_some();
+
+					if completing && code == KeyCode::Enter {
+						break;
+					}
+				}
+
+				// while they're searching through their history, the search gets every key
+				let searching = self.selected_box != DisplayBox::ComposeAddress
+					&& self.selected_box != DisplayBox::ComposeBody;
+
+				if searching && self.input_view.route_search_key(code, modifiers) {
+					break;
+				}
+
+				if searching && code == KeyCode::Char('r') && modifiers == KeyModifiers::CONTROL {
+					self.input_view.start_search();
+					break;
+				}
+
+				let undo = Utilities::key_matches(&self.settings.undo_key, code, modifiers);
+				if undo || Utilities::key_matches(&self.settings.redo_key, code, modifiers) {
+					let view = self.selected_input();
+
+					if undo {
+						view.undo();
+					} else {
+						view.redo();
+					}
+					break;
+				}
+
+				// this is checked before the readline shortcuts so that it still
+				// works if they set it to one of those
+				if Utilities::key_matches(&self.settings.editor_key, code, modifiers) {
+					self.edit_current_input();
+					break;
+				}
+
+				// if they're using vi mode, that gets the first chance to handle the key
+				if self.settings.vi_mode && self.selected_input().route_vi_key(code, modifiers) {
+					break;
+				}
+
+				// the readline shortcuts (ctrl+a, ctrl+w, etc) work the same in every
+				// input box, so just give them to whichever one is selected
+				let edited = match self.selected_box {
+					DisplayBox::ComposeAddress => {
+						self.address_view.route_editing_key(code, modifiers)
+					}
+					DisplayBox::ComposeBody => {
+						self.compose_body_view.route_editing_key(code, modifiers)
+					}
+					_ => {
+						let edited = self.input_view.route_editing_key(code, modifiers);
+						if edited && self.input_view.input.is_empty() {
+							self.send_typing_in_current(false).await;
+						}
+						edited
+					}
+				};
+
+				if edited {
+					break;
+				}
+
 				match code {
 					// each view treats these keycodes the same, so just
 					// route it through the correct one.
@@ -508,10 +788,30 @@ impl MainApp {
 						match self.selected_box {
 							DisplayBox::ComposeBody => self.compose_body_view.route_keycode(code),
 
+							DisplayBox::ComposeAddress if code == KeyCode::Tab => {
+								self.tab_complete(true)
+							}
 							DisplayBox::ComposeAddress => self.address_view.route_keycode(code),
 
 							_ => {
-								self.input_view.route_keycode(code);
+								if code == KeyCode::Esc {
+									// with nothing typed, esc gets rid of the chat filters
+									if self.input_view.input.is_empty()
+										&& self.chats_view.is_filtered()
+									{
+										self.chats_view.clear_filters();
+										hint!("cleared the chat filters");
+									}
+
+									self.stash_draft();
+								}
+
+								if code == KeyCode::Tab {
+									self.tab_complete(true);
+								} else {
+									self.input_view.route_keycode(code);
+								}
+
 								if code == KeyCode::Backspace && self.input_view.input.is_empty() {
 									self.send_typing_in_current(false).await;
 								}
@@ -519,6 +819,29 @@ impl MainApp {
 						};
 					}
 
+					// shift+tab goes backwards through the completions
+					KeyCode::BackTab => {
+						if self.selected_box != DisplayBox::ComposeBody {
+							self.tab_complete(false);
+						}
+					}
+					// alt+enter (or shift+enter, in the terminals that tell us about it)
+					// adds a new line instead of sending what they've typed
+					KeyCode::Enter
+						if self.settings.multiline_input
+							&& (modifiers.contains(KeyModifiers::ALT)
+								|| modifiers.contains(KeyModifiers::SHIFT)) =>
+					{
+						match self.selected_box {
+							DisplayBox::ComposeBody => self.compose_body_view.append_char('\n'),
+							DisplayBox::ComposeAddress => (),
+							_ => {
+								if !self.input_view.input.is_empty() {
+									self.input_view.append_char('\n');
+								}
+							}
+						}
+					}
 					KeyCode::Enter => {
 						match self.selected_box {
 							DisplayBox::ComposeAddress => {
@@ -526,21 +849,24 @@ impl MainApp {
 								// address box to the body box; loads in
 								// the messages to the msgs_view
 								// just like in the real iMessage app.
-								self.selected_box = DisplayBox::ComposeBody;
+								// But only if they're actually sending it somewhere
+								if let Some(chat) = self.compose_recipients() {
+									self.selected_box = DisplayBox::ComposeBody;
 
-								let _ = self
-									.msgs_view
-									.load_in_conversation(&self.address_view.input)
-									.await;
+									let _ = self.msgs_view.load_in_conversation(&chat).await;
+								}
 							}
 							DisplayBox::ComposeBody => {
-								let chat = &self.address_view.input;
+								let chat = match self.compose_recipients() {
+									Some(chat) => chat,
+									None => break,
+								};
 
 								// if you hit enter when you're already
 								// in the compose
 								// body, just send it.
 								self.send_text(
-									Some(chat.to_owned()),
+									Some(chat),
 									Some(self.compose_body_view.input.to_owned()),
 									None,
 								)
@@ -571,6 +897,8 @@ impl MainApp {
 							_ => {
 								if !self.input_view.input.is_empty() {
 									self.handle_full_input().await;
+								} else if self.selected_box == DisplayBox::Attachments {
+									self.attachments_view.open_current(&self.settings);
 								}
 							}
 						}
@@ -599,12 +927,20 @@ impl MainApp {
 						}
 					}
 					KeyCode::Up | KeyCode::Down => {
-						if self.selected_box != DisplayBox::ComposeBody
-							&& self.selected_box != DisplayBox::ComposeAddress
-						{
-							// tab up/down to more recent/less
-							// recent executed command
-							self.input_view.change_command(code == KeyCode::Up);
+						let up = code == KeyCode::Up;
+
+						match self.selected_box {
+							DisplayBox::ComposeBody => self.compose_body_view.move_vertically(up),
+							DisplayBox::ComposeAddress => (),
+							_ => {
+								// if they've typed multiple lines, move between them.
+								// Else, tab up/down to more recent/less recent executed command
+								if self.input_view.is_multiline(&self.settings) {
+									self.input_view.move_vertically(up);
+								} else {
+									self.input_view.change_command(up);
+								}
+							}
 						}
 					}
 					// ctrl+c gets hijacked by crossterm, so I wanted to manually
@@ -621,6 +957,13 @@ impl MainApp {
 								}
 								_ => self.quit_app = true,
 							}
+						} else if modifiers == KeyModifiers::CONTROL && c == 'v' {
+							match self.selected_box {
+								DisplayBox::ComposeAddress | DisplayBox::ComposeBody => {
+									hint!("you can only paste attachments into existing chats")
+								}
+								_ => self.paste_attachments(),
+							}
 						} else if c.is_digit(10)
 							&& self.input_view.input.is_empty()
 							&& self.selected_box != DisplayBox::ComposeBody
@@ -651,6 +994,11 @@ impl MainApp {
 			}
 		}
 
+		// while they're typing a `/` filter, narrow down the chats as they go
+		if let Some(filter) = self.input_view.input.strip_prefix('/') {
+			self.chats_view.set_filter(Some(filter));
+		}
+
 		Ok(())
 	}
 
@@ -658,7 +1006,7 @@ impl MainApp {
 		// handle single character that is not a control key
 		// this is only executed if the selected view is not the
 		// compose address view and not the compose body view
-		if !self.input_view.input.is_empty() || ch == ':' {
+		if !self.input_view.input.is_empty() || ch == ':' || ch == '/' {
 			self.input_view.append_char(ch);
 
 			let graphemes = self.input_view.input.graphemes(true).collect::<Vec<&str>>();
@@ -675,14 +1023,34 @@ impl MainApp {
 		} else {
 			match ch {
 				'h' | 'l' => self.switch_selected_box(),
-				// quit out of help display if it is showing
-				'q' | 'Q' => {
-					if let DisplayBox::Help = self.selected_box {
-						self.selected_box = DisplayBox::Chats;
-					}
-				}
+				// quit out of help display (or attachments) if it is showing
+				'q' | 'Q' => match self.selected_box {
+					DisplayBox::Help => self.selected_box = DisplayBox::Chats,
+					DisplayBox::Attachments => self.selected_box = DisplayBox::Messages,
+					_ => {}
+				},
 				// scroll up or down in the selected box
 				'k' | 'j' => self.scroll(ch == 'k', distance).await,
+				// these toggle which chats are shown
+				'u' if self.selected_box == DisplayBox::Chats => {
+					self.chats_view.toggle_unread_only()
+				}
+				'g' if self.selected_box == DisplayBox::Chats => {
+					self.chats_view.toggle_groups_only()
+				}
+				'a' if self.selected_box == DisplayBox::Chats => self.chats_view.toggle_archived(),
+				// and these only mean something in the attachments browser
+				_ if self.selected_box == DisplayBox::Attachments => match ch {
+					' ' => self.attachments_view.toggle_mark(),
+					'd' => {
+						let dir = self.download_dir();
+						self.attachments_view.download_marked(dir, &self.settings)
+					}
+					'o' => self.attachments_view.open_current(&self.settings),
+					'y' => self.attachments_view.copy_current_path(),
+					'f' => self.attachments_view.cycle_filter(),
+					_ => {}
+				},
 				// will add more later maybe
 				_ => {}
 			}
@@ -702,28 +1070,57 @@ impl MainApp {
 	}
 
 	async fn handle_full_input(&mut self) {
+		// cmd is the first bit before a space, e.g. the ':s' in ':s hey friend'
+		let input = self.input_view.input.to_owned();
+
+		// the chats are filtered as they type after a `/`, so enter just keeps
+		// the filter and gets rid of the input
+		if let Some(filter) = input.strip_prefix('/') {
+			self.chats_view.set_filter(Some(filter));
+			self.input_view.handle_escape();
+			return;
+		}
+
 		// add the command that it's handling to the most recent commands
 		// so you can tab up to it
 		self.input_view
-			.last_commands
-			.insert(0, self.input_view.input.to_owned());
+			.add_to_history(&input, self.settings.history_size);
 
-		// cmd is the first bit before a space, e.g. the ':s' in ':s hey friend'
-		let mut splits = self.input_view.input.split(' ').collect::<Vec<&str>>();
+		let mut splits = input.split(' ').collect::<Vec<&str>>();
 		let cmd = splits.drain(0..1).as_slice()[0];
 
+		// what to put in the input once the command has been run, if anything
+		let mut next_input = None;
+
 		match cmd.to_lowercase().as_str() {
 			// quit the app
 			":q" => self.quit_app = true,
 			// select a chat
 			":c" => {
-				if !splits.is_empty() && !splits[0].is_empty() {
-					match splits[0].parse::<usize>() {
-						Ok(idx) => self.load_in_conversation(idx).await,
-						Err(_) => hint!("Cannot convert '{}' to an int", splits[0]),
+				// they can give the index, or some of the name. If the name could
+				// be more than one chat, let them pick which one they meant
+				let query = splits.join(" ");
+				let chats = &self.chats_view.chats;
+
+				let idx = match query.trim().parse::<usize>() {
+					Ok(idx) => Some(idx),
+					Err(_) if query.trim().is_empty() => None,
+					Err(_) => ChatPicker::best_match(&query, chats),
+				};
+
+				match idx {
+					Some(idx) => {
+						self.load_in_conversation(idx).await;
+						next_input = self.take_current_draft();
+					}
+					None => {
+						let picker = ChatPicker::new(query.trim(), chats);
+						if picker.results.is_empty() {
+							hint!("no chats match '{}'", query.trim());
+						} else {
+							self.picker = Some(picker);
+						}
 					}
-				} else {
-					hint!("Please insert an index");
 				}
 			}
 			// show help
@@ -736,6 +1133,11 @@ impl MainApp {
 			// reload the chats and redraw anything in case of
 			// graphical inconsistencies
 			":r" => {
+				// names could have changed too, so look them all up again
+				if let Ok(mut state) = STATE.write() {
+					state.names.clear();
+				}
+
 				self.redraw_all = true;
 				self.chats_view.reload_chats().await;
 			}
@@ -751,15 +1153,36 @@ impl MainApp {
 			":a" => {
 				if !splits.is_empty() {
 					match splits[0].parse::<usize>() {
-						Ok(idx) => self.msgs_view.open_attachment(idx),
+						Ok(idx) => {
+							let dir = self.download_dir();
+							self.msgs_view.download_attachment(idx, dir, &self.settings)
+						}
+						Err(_) => hint!("Cannot convert {} to an int", splits[0]),
+					}
+				} else {
+					// without an index, show all of them instead
+					self.attachments_view.load(&self.msgs_view.messages);
+					self.selected_box = DisplayBox::Attachments;
+					hint!(
+						"space marks, d downloads, o opens, y copies the path, f filters, q closes"
+					);
+				}
+			}
+			// open a link by index
+			":o" => {
+				if !splits.is_empty() {
+					match splits[0].parse::<usize>() {
+						Ok(idx) => self.msgs_view.open_link(idx, &self.settings),
 						Err(_) => hint!("Cannot convert {} to an int", splits[0]),
 					}
 				} else {
-					hint!("Please input an index (e.g. ':a 2')");
+					hint!("Please input an index (e.g. ':o 2')");
 				}
 			}
 			// send files
 			":f" => self.send_attachments(splits).await,
+			// send whatever's in the clipboard
+			":paste" => self.paste_attachments(),
 			// send a tapback
 			":t" => {
 				let tapback = splits.join("");
@@ -782,71 +1205,165 @@ impl MainApp {
 			}
 			// delete a text
 			":dt" => {
-				if let Some(ls) = self.selected_chat {
-					if self.msgs_view.delete_current_text().await {
-						// we could `join!` these async stuff but they happen basically instantly
-						// (since everything is thrown onto tokio) and it's not worth the string
-						// copy that would be necessary
-						self.chats_view.reload_chats().await;
-
-						let chat = &self.chats_view.chats[ls].chat_identifier;
-
-						self.msgs_view.load_in_conversation(chat).await;
-					}
+				if self.selected_chat.is_some() && !self.msgs_view.messages.is_empty() {
+					self.confirm(
+						"Are you sure you want to delete the selected text?".to_owned(),
+						ConfirmAction::DeleteText,
+					)
+					.await;
 				}
 			}
 			// delete a conversation
 			":dc" => {
-				if !splits.is_empty() {
-					// this is if they specified a conversation to delete
-					let chat = splits[0];
+				// delete the conversation they specified, or the currently
+				// selected one if they didn't specify one
+				let specified = !splits.is_empty() && !splits[0].is_empty();
 
-					let mut api = self.client.write().await;
+				let chat = if specified {
+					Some(splits[0].to_owned())
+				} else {
+					self.selected_chat
+						.map(|ls| self.chats_view.chats[ls].chat_identifier.to_owned())
+				};
 
-					let success = match api.delete_chat(chat).await {
-						Err(err) => {
-							hint!("Failed to delete conversation : {}", err);
-							false
-						}
-						Ok(_) => {
-							hint!("deleted conversation :)");
+				match chat {
+					Some(chat) if specified || self.settings.confirm_destructive => {
+						self.confirm(
+							format!("Are you sure you want to delete the conversation {}?", chat),
+							ConfirmAction::DeleteConversation(chat),
+						)
+						.await;
+					}
+					Some(chat) => {
+						// if they turned off the prompts, make them type out the
+						// conversation so that they can't delete it by accident
+						hint!(
+							"Please enter ':dc {}' if you'd like \
+						to delete this conversation",
+							chat
+						);
+					}
+					None => hint!("Please select or specify a conversation to delete"),
+				}
+			}
+			// pin or unpin the chat they specified, or the current one
+			":pin" | ":unpin" => {
+				let pin = cmd.to_lowercase() == ":pin";
 
-							// reload chats so that it doesn't show up anymore
-							//self.chats_view.reload_chats().await;
-							true
-						}
-					};
+				let idx = match splits.first().filter(|s| !s.is_empty()) {
+					Some(s) => s.parse::<usize>().ok(),
+					None => self.selected_chat,
+				};
 
-					drop(api);
+				match idx {
+					Some(idx) if idx < self.chats_view.chats.len() => {
+						self.chats_view.set_pinned(idx, pin);
 
-					if success {
-						self.chats_view.reload_chats().await;
+						let name = &self.chats_view.chats[idx].display_name;
+						hint!(
+							"{} {}",
+							if pin {
+								"pinned"
+							} else {
+								"unpinned"
+							},
+							name
+						);
+					}
+					Some(idx) => hint!("{} is out of range for the chats", idx),
+					None => hint!(
+						"Please open a chat or give the index of one to {}",
+						&cmd[1..]
+					),
+				}
+			}
+			// give the current chat a nickname, or get rid of it
+			":alias" => self.set_alias(&splits.join(" ")).await,
+			// show everyone in the current chat
+			":members" => self.show_members().await,
+			// stop notifications for the current chat, for however long they say
+			":mute" | ":unmute" => {
+				let chat = self
+					.selected_chat
+					.map(|idx| self.chats_view.chats[idx].chat_identifier.to_owned());
+
+				// with no duration, it's muted until they unmute it
+				let duration = splits.join("");
+				let until = if duration.is_empty() {
+					Some(0)
+				} else {
+					Utilities::parse_duration(&duration)
+						.and_then(|secs| chrono::Local::now().timestamp().checked_add(secs))
+				};
 
-						if let Some(ls) = self.selected_chat {
-							let sel_chat = &self.chats_view.chats[ls].chat_identifier;
+				match (chat, until) {
+					(None, _) => hint!("Please open the chat you'd like to {}", &cmd[1..]),
+					(Some(chat), _) if cmd.to_lowercase() == ":unmute" => {
+						self.chats_view.set_muted(&chat, None);
+						hint!("unmuted this chat");
+					}
+					(Some(chat), Some(until)) => {
+						self.chats_view.set_muted(&chat, Some(until));
 
-							if sel_chat.as_str() == chat {
-								self.msgs_view.load_in_conversation("").await;
-							}
+						if duration.is_empty() {
+							hint!("muted this chat until you unmute it");
+						} else {
+							hint!("muted this chat for {}", duration);
 						}
-
-						self.selected_chat = None;
 					}
-				} else if let Some(ls) = self.selected_chat {
-					// if they didn't specify a conversation,
-					// let them know how to delete this conversation
-					let chat = &self.chats_view.chats[ls].chat_identifier;
+					(Some(_), None) => hint!(
+						"Cannot understand '{}'; try something like 30m, 2h, or 1d",
+						duration
+					),
+				}
+			}
+			// hide the current chat (or the one they give) until a new text comes in
+			":archive" | ":unarchive" => {
+				let archive = cmd.to_lowercase() == ":archive";
 
-					hint!(
-						"Please enter ':dc {}' if you'd like \
-					to delete this conversation",
-						chat
-					);
+				let idx = match splits.first().filter(|s| !s.is_empty()) {
+					Some(s) => s.parse::<usize>().ok(),
+					None => self.selected_chat,
+				};
+
+				match idx {
+					Some(idx) if idx < self.chats_view.chats.len() => {
+						let chat = self.chats_view.chats[idx].chat_identifier.to_owned();
+						self.chats_view.set_archived(&chat, archive);
+
+						let verb = if archive {
+							"archived"
+						} else {
+							"unarchived"
+						};
+						hint!("{} {}", verb, self.chats_view.chats[idx].display_name);
+					}
+					Some(idx) => hint!("{} is out of range for the chats", idx),
+					None => hint!(
+						"Please open a chat or give the index of one to {}",
+						&cmd[1..]
+					),
 				}
 			}
 			// copy the text of the currently selected message
 			// to the system clipboard
 			":y" => self.msgs_view.copy_current_to_clipboard(),
+			// write the text in their editor, then put it back in the input to send
+			":e" => {
+				if let Some(text) = self.edit_in_editor(&splits.join(" ")) {
+					next_input = Some(format!(":s {}", text));
+				}
+			}
+			// or send it as soon as they're done writing it
+			":es" => {
+				if let Some(text) = self.edit_in_editor(&splits.join(" ")) {
+					if text.trim().is_empty() {
+						hint!("not sending an empty text");
+					} else {
+						self.send_text(None, Some(text), None).await;
+					}
+				}
+			}
 			// default
 			x => {
 				hint!("Command {} not recognized", x);
@@ -855,6 +1372,412 @@ impl MainApp {
 
 		// to reset the input view to no input
 		self.input_view.handle_escape();
+
+		if let Some(input) = next_input {
+			self.input_view.set_input(input);
+		}
+	}
+
+	fn edit_in_editor(&mut self, text: &str) -> Option<String> {
+		let res = Utilities::edit_in_editor(text);
+
+		// the editor draws all over the screen, so everything has to be redrawn
+		self.redraw_all = true;
+
+		match res {
+			Ok(text) => Some(text),
+			Err(err) => {
+				hint!("could not edit in your editor: {}", err);
+				None
+			}
+		}
+	}
+
+	fn stash_draft(&mut self) -> bool {
+		// save the text they're writing as a draft for the current chat. Returns
+		// whether or not there was a text to save
+		let chat = match read_state!().current_chat.to_owned() {
+			Some(chat) => chat,
+			None => return false,
+		};
+
+		let input = self.input_view.input.to_owned();
+		let is_text = input.to_lowercase().starts_with(":s ");
+
+		if is_text {
+			let draft = &input[3..];
+			self.chats_view.set_draft(&chat, draft);
+
+			if !draft.trim().is_empty() {
+				hint!("saved your draft for this chat");
+			}
+		}
+
+		is_text
+	}
+
+	fn take_current_draft(&mut self) -> Option<String> {
+		// get the draft for the current chat, ready to be put back in the input
+		let chat = read_state!().current_chat.to_owned()?;
+		let draft = self.chats_view.take_draft(&chat)?;
+
+		hint!("restored your draft for this chat");
+		Some(format!(":s {}", draft))
+	}
+
+	fn tab_complete(&mut self, forward: bool) {
+		// the compose address box completes the people they could be texting
+		if self.selected_box == DisplayBox::ComposeAddress {
+			let view = &mut self.address_view;
+
+			let cycling = matches!(view.completion, Some(ref comp) if comp.completed == view.input);
+
+			if !cycling {
+				view.completion = if view.right_offset == 0 {
+					Completion::for_recipients(&view.input, &self.chats_view.chats)
+				} else {
+					None
+				};
+
+				if view.completion.is_none() || view.complete_prefix() {
+					return;
+				}
+			}
+
+			view.cycle_completion(forward);
+			return;
+		}
+
+		// keep going through the same candidates if they haven't typed anything since
+		// the last tab. Else, find what the word they're typing could be.
+		let cycling = matches!(
+			self.input_view.completion,
+			Some(ref comp) if comp.completed == self.input_view.input
+		);
+
+		if !cycling {
+			// only the end of the input can be completed
+			self.input_view.completion = if self.input_view.right_offset == 0 {
+				Completion::for_input(
+					&self.input_view.input,
+					&self.chats_view.chats,
+					&self.settings,
+				)
+			} else {
+				None
+			};
+
+			if self.input_view.completion.is_none() {
+				// don't put a tab in the middle of a path they're trying to complete
+				if self.input_view.input.to_lowercase().starts_with(":f ") {
+					hint!("no files match that path");
+				} else {
+					self.input_view.handle_tab();
+				}
+				return;
+			}
+
+			// fill in as much as all the candidates have in common before cycling
+			if self.input_view.complete_prefix() {
+				return;
+			}
+		}
+
+		self.input_view.cycle_completion(forward);
+	}
+
+	fn compose_recipients(&self) -> Option<String> {
+		// get the addresses that they've typed into the compose address box,
+		// making sure that each one looks like a phone number or email. They
+		// can also type the nicknames they've given people, or the identifiers
+		// of chats that already exist
+		let addresses = NameCache::unaliased(&self.address_view.input);
+		let mut recipients = Vec::new();
+
+		for address in addresses.split(',').map(|a| a.trim()) {
+			if address.is_empty() {
+				continue;
+			}
+
+			if self
+				.chats_view
+				.chats
+				.iter()
+				.any(|c| c.chat_identifier == address)
+			{
+				recipients.push(address.to_owned());
+				continue;
+			}
+
+			match Utilities::normalize_address(address) {
+				Some(normalized) => recipients.push(normalized),
+				None => {
+					hint!("'{}' doesn't look like a phone number or email", address);
+					return None;
+				}
+			}
+		}
+
+		if recipients.is_empty() {
+			hint!("Please enter who you'd like to send this to");
+			return None;
+		}
+
+		Some(recipients.join(","))
+	}
+
+	async fn set_alias(&mut self, alias: &str) {
+		let idx = match self.selected_chat {
+			Some(idx) => idx,
+			None => {
+				hint!("Please open the chat you'd like to give a nickname");
+				return;
+			}
+		};
+
+		let id = self.chats_view.chats[idx].chat_identifier.to_owned();
+		let alias = alias.trim();
+
+		if let Ok(mut state) = STATE.write() {
+			state.names.set_alias(
+				&id,
+				if alias.is_empty() {
+					None
+				} else {
+					Some(alias)
+				},
+			);
+		}
+
+		// without the alias, it goes back to whatever the server calls it
+		let name = if alias.is_empty() {
+			NameCache::get_name(&self.client, &id)
+				.await
+				.unwrap_or_else(|_| id.to_owned())
+		} else {
+			alias.to_owned()
+		};
+
+		self.chats_view.chats[idx].display_name = name.to_owned();
+		self.chats_view.last_height = 0;
+
+		if alias.is_empty() {
+			hint!("removed the nickname for {}", name);
+		} else {
+			hint!("{} will now be shown as {}", id, name);
+		}
+	}
+
+	async fn show_members(&mut self) {
+		let chat = match self.selected_chat {
+			Some(idx) => &self.chats_view.chats[idx],
+			None => {
+				hint!("Please open a chat to see who's in it");
+				return;
+			}
+		};
+
+		// the addresses are all in one string, separated by commas
+		let addresses = chat
+			.addresses
+			.split(',')
+			.map(|a| a.trim().to_owned())
+			.filter(|a| !a.is_empty())
+			.collect::<Vec<String>>();
+
+		let mut members = Vec::new();
+
+		for address in addresses {
+			let name = match NameCache::get_name(&self.client, &address).await {
+				Ok(name) if !name.is_empty() => name,
+				_ => address.to_owned(),
+			};
+
+			members.push((name, address));
+		}
+
+		if members.is_empty() {
+			hint!("couldn't find anyone in this chat");
+		} else {
+			self.members = Some(members);
+		}
+	}
+
+	fn draw_members(
+		frame: &mut Frame<CrosstermBackend<Stdout>>,
+		members: &[(String, String)],
+		size: Rect,
+		settings: &Settings,
+	) {
+		// a list of everyone in the chat, with the same color that their texts have
+		let colorscheme = &settings.colorscheme;
+
+		let lines = members
+			.iter()
+			.map(|(name, address)| {
+				let color = colorscheme
+					.sender_color(name)
+					.unwrap_or(colorscheme.text_color);
+
+				let mut spans = vec![
+					Span::styled("● ", Style::default().fg(color)),
+					Span::styled(name.as_str(), Style::default().fg(colorscheme.text_color)),
+				];
+
+				if name != address {
+					spans.push(Span::styled(
+						format!("  {}", address),
+						Style::default().fg(colorscheme.hints_box),
+					));
+				}
+
+				Spans::from(spans)
+			})
+			.collect::<Vec<Spans>>();
+
+		let width = members
+			.iter()
+			.map(|(n, a)| UnicodeWidthStr::width(n.as_str()) + UnicodeWidthStr::width(a.as_str()))
+			.max()
+			.unwrap_or(0) as u16
+			+ 8;
+		let popup = Utilities::centered_rect(max(width, 30), members.len() as u16 + 2, size);
+
+		let widget = Paragraph::new(lines).block(
+			Block::default()
+				.title(format!("| members ({}) |", members.len()))
+				.borders(Borders::ALL)
+				.border_type(BorderType::Rounded)
+				.border_style(Style::default().fg(colorscheme.selected_box)),
+		);
+
+		frame.render_widget(Clear, popup);
+		frame.render_widget(widget, popup);
+	}
+
+	fn selected_input(&mut self) -> &mut InputView {
+		// the input box that keys should be typed into right now
+		match self.selected_box {
+			DisplayBox::ComposeAddress => &mut self.address_view,
+			DisplayBox::ComposeBody => &mut self.compose_body_view,
+			_ => &mut self.input_view,
+		}
+	}
+
+	fn edit_current_input(&mut self) {
+		// open whatever they're typing in their editor, then put it back
+		match self.selected_box {
+			DisplayBox::ComposeAddress => hint!("you can only edit the body of a message"),
+			DisplayBox::ComposeBody => {
+				let body = self.compose_body_view.input.to_owned();
+
+				if let Some(text) = self.edit_in_editor(&body) {
+					self.compose_body_view.set_input(text);
+				}
+			}
+			_ => {
+				// only the body of the text is edited, but they can still edit
+				// other commands if that's what they're typing
+				let input = self.input_view.input.to_owned();
+				let is_text = input.is_empty() || input.to_lowercase().starts_with(":s ");
+				let body = if is_text {
+					input.get(3..).unwrap_or_default()
+				} else {
+					&input
+				};
+
+				if let Some(text) = self.edit_in_editor(body) {
+					self.input_view.set_input(if is_text {
+						format!(":s {}", text)
+					} else {
+						text
+					});
+				}
+			}
+		}
+	}
+
+	async fn confirm(&mut self, prompt: String, action: ConfirmAction) {
+		// show the prompt so that the user has to confirm the action before
+		// it runs, unless they've turned the prompts off
+		if self.settings.confirm_destructive {
+			self.confirmation = Some(Confirmation { prompt, action });
+		} else {
+			self.run_action(action).await;
+		}
+	}
+
+	async fn run_action(&mut self, action: ConfirmAction) {
+		match action {
+			ConfirmAction::DeleteText => self.delete_current_text().await,
+			ConfirmAction::DeleteConversation(chat) => self.delete_conversation(&chat).await,
+			ConfirmAction::SendFiles(files) => self.send_text(None, None, Some(files)).await,
+			ConfirmAction::SendPasted(files) => {
+				self.send_text_with(None, None, Some(files), true).await
+			}
+		}
+	}
+
+	async fn delete_current_text(&mut self) {
+		if let Some(ls) = self.selected_chat {
+			if self.msgs_view.delete_current_text().await {
+				// we could `join!` these async stuff but they happen basically instantly
+				// (since everything is thrown onto tokio) and it's not worth the string
+				// copy that would be necessary
+				self.chats_view.reload_chats().await;
+
+				let chat = &self.chats_view.chats[ls].chat_identifier;
+
+				self.msgs_view.load_in_conversation(chat).await;
+			}
+		}
+	}
+
+	async fn delete_conversation(&mut self, chat: &str) {
+		let mut api = self.client.write().await;
+
+		let success = match api.delete_chat(chat).await {
+			Err(err) => {
+				hint!("Failed to delete conversation : {}", err);
+				false
+			}
+			Ok(_) => {
+				hint!("deleted conversation :)");
+				true
+			}
+		};
+
+		drop(api);
+
+		if success {
+			// reload chats so that it doesn't show up anymore
+			self.chats_view.reload_chats().await;
+
+			if let Some(ls) = self.selected_chat {
+				let sel_chat = &self.chats_view.chats[ls].chat_identifier;
+
+				if sel_chat.as_str() == chat {
+					self.msgs_view.load_in_conversation("").await;
+				}
+			}
+
+			self.selected_chat = None;
+		}
+	}
+
+	fn download_dir(&self) -> PathBuf {
+		// get the directory for the current chat's attachments
+		let chat_id = read_state!().current_chat.to_owned().unwrap_or_default();
+
+		let chat_name = self
+			.chats_view
+			.chats
+			.iter()
+			.find(|c| c.chat_identifier == chat_id)
+			.map(|c| c.display_name.as_str())
+			.unwrap_or(&chat_id);
+
+		AttachmentsView::download_dir(&self.settings, &chat_id, chat_name)
 	}
 
 	fn switch_selected_box(&mut self) {
@@ -871,6 +1794,7 @@ impl MainApp {
 		match self.selected_box {
 			DisplayBox::Chats => self.chats_view.scroll(up, distance).await,
 			DisplayBox::Messages => self.msgs_view.scroll(up, distance).await,
+			DisplayBox::Attachments => self.attachments_view.scroll(up, distance),
 			DisplayBox::Help => {
 				// these comparisons are to ensure it doesn't scroll too far
 				if up {
@@ -889,6 +1813,12 @@ impl MainApp {
 	async fn load_in_conversation(&mut self, idx: usize) {
 		// ensure that it's in range
 		if idx < self.chats_view.chats.len() {
+			// if they were writing a text, save it for the chat they were
+			// writing it in, so that it doesn't get sent to this one
+			if self.stash_draft() {
+				self.input_view.handle_escape();
+			}
+
 			// first tell the chats view to load it in
 			self.chats_view.load_in_conversation(idx);
 			let id = self.chats_view.chats[idx].chat_identifier.to_owned();
@@ -904,6 +1834,12 @@ impl MainApp {
 
 			loaded.await;
 
+			if self.input_view.input.is_empty() {
+				if let Some(draft) = self.take_current_draft() {
+					self.input_view.set_input(draft);
+				}
+			}
+
 			hint!("loading in messages...");
 		} else {
 			hint!("{} is out of range for the chats", idx);
@@ -919,16 +1855,13 @@ impl MainApp {
 				let past = self.chats_view.new_text(&text).await;
 
 				let name = match &text.sender {
-					Some(name) => name.to_owned(),
+					Some(name) => NameCache::aliased(name),
 					None => {
 						let chat_id = text.chat_identifier.as_ref().unwrap().to_owned();
 
-						let mut api = self.client.write().await;
-
-						if let Ok(nm) = api.get_name(&chat_id).await {
-							nm
-						} else {
-							chat_id
+						match NameCache::get_name(&self.client, &chat_id).await {
+							Ok(name) => name,
+							Err(_) => chat_id,
 						}
 					}
 				};
@@ -937,7 +1870,13 @@ impl MainApp {
 
 				// only show notification if it's not from me &&
 				// they want notifications
-				let show_notif = self.settings.notifications && !text.is_from_me;
+				let show_notif = self.settings.notifications
+					&& !text.is_from_me
+					&& !text
+						.chat_identifier
+						.as_ref()
+						.map(|id| self.chats_view.is_muted(id))
+						.unwrap_or(false);
 
 				// load_in will be true if I just composed and sent a
 				// conversation. It's a kinda hacky workaround to prevent text
@@ -988,9 +1927,9 @@ impl MainApp {
 				if let Some(ref id) = text.chat_identifier {
 					// need to grab name now 'cause `text` is moved
 					// into msgs_view
-					if text.message_type == MessageType::Typing {
-						let name = text.sender.as_ref().unwrap_or(id);
-						Utilities::show_notification(name, &format!("{} is typing...", name));
+					if text.message_type == MessageType::Typing && !self.chats_view.is_muted(id) {
+						let name = NameCache::aliased(text.sender.as_ref().unwrap_or(id));
+						Utilities::show_notification(&name, &format!("{} is typing...", name));
 					}
 
 					// if we have selected a chat...
@@ -1019,6 +1958,14 @@ impl MainApp {
 			_ => return,
 		}
 
+		// the server doesn't know about their nicknames, so put them in
+		for chat in self.chats_view.chats.iter_mut() {
+			let alias = NameCache::aliased(&chat.chat_identifier);
+			if alias != chat.chat_identifier {
+				chat.display_name = alias;
+			}
+		}
+
 		self.chats_view.last_height = 0;
 
 		hint!("loaded in chats :)");
@@ -1048,10 +1995,20 @@ impl MainApp {
 	}
 
 	async fn send_text(
-		&self,
+		&mut self,
+		chat_id: Option<String>,
+		text: Option<String>,
+		files: Option<Vec<String>>,
+	) {
+		self.send_text_with(chat_id, text, files, false).await;
+	}
+
+	async fn send_text_with(
+		&mut self,
 		chat_id: Option<String>,
 		text: Option<String>,
 		files: Option<Vec<String>>,
+		temporary: bool,
 	) {
 		// make chat_id an option so that it can be manually specified
 		// for when you're making a new conversation, or omitted,
@@ -1068,12 +2025,30 @@ impl MainApp {
 
 		// only send it if you have a chat
 		if let Some(id) = chat_option {
+			// whatever was saved as a draft for this chat has been sent now (or
+			// replaced by what was sent), so it shouldn't come back next time
+			if text.is_some() {
+				self.chats_view.set_draft(&id, "");
+			}
+
 			let api_clone = self.client.clone();
 
+			// files that were only made to be sent (like pasted images)
+			// are deleted once they're sent, whether it worked or not
+			let to_remove = match files {
+				Some(ref files) if temporary => files.to_owned(),
+				_ => Vec::new(),
+			};
+
 			tokio::spawn(async move {
 				let mut api = api_clone.write().await;
 
 				let res = api.send_message(id, text, None, files, None).await;
+				drop(api);
+
+				for file in to_remove.iter() {
+					let _ = std::fs::remove_file(file);
+				}
 
 				match res {
 					Ok(_) => hint!("text sent :)"),
@@ -1085,7 +2060,32 @@ impl MainApp {
 		}
 	}
 
-	async fn send_attachments(&self, files: Vec<&str>) {
+	fn paste_attachments(&mut self) {
+		// send the image or files in the clipboard to the current chat,
+		// once they've confirmed that it's what they want to send
+		if self.selected_chat.is_none() {
+			hint!("please select a chat to paste into first");
+			return;
+		}
+
+		match Paste::from_clipboard() {
+			Ok(pasted) => {
+				// this is always confirmed, even if they've turned confirmations off,
+				// since it's so easy to have something unexpected in the clipboard
+				self.confirmation = Some(Confirmation {
+					prompt: format!("send {} from the clipboard?", pasted.description),
+					action: if pasted.temporary {
+						ConfirmAction::SendPasted(pasted.files)
+					} else {
+						ConfirmAction::SendFiles(pasted.files)
+					},
+				});
+			}
+			Err(err) => hint!("could not paste: {}", err),
+		}
+	}
+
+	async fn send_attachments(&mut self, files: Vec<&str>) {
 		let orig = files.join(" ");
 
 		// this retuns a vector of strings, each string specifying the path
@@ -1113,14 +2113,16 @@ impl MainApp {
 		new_ops.push(val.join(" "));
 
 		self.settings.parse_args(new_ops, true, false);
+
+		// some settings change how the chats are drawn, so make sure they're redrawn
+		self.chats_view.last_height = 0;
 	}
 
 	pub async fn send_tapback(&self, tap: &str) {
-		const MSGS: [&str; 6] = ["love", "like", "dislike", "laugh", "emphasize", "question"];
 		let guid = &self.msgs_view.messages[self.msgs_view.selected_msg as usize].guid;
 
 		// ensure that the tapback type that they specified is in the options
-		if let Some(idx) = MSGS.iter().position(|c| *c == tap) {
+		if let Some(idx) = TAPBACKS.iter().position(|c| *c == tap) {
 			// ensure that we've actually selected a conversation
 			if self.chats_view.last_selected.is_some() {
 				let mut api = self.client.write().await;
@@ -1134,7 +2136,7 @@ impl MainApp {
 			hint!(
 				"Did not recognize tapback option {}; possible options are: {}",
 				tap,
-				MSGS.join(", ")
+				TAPBACKS.join(", ")
 			);
 		}
 	}
@@ -1145,10 +2147,36 @@ enum DisplayBox {
 	Chats,
 	Messages,
 	Help,
+	Attachments,
 	ComposeAddress,
 	ComposeBody,
 }
 
+// an action that has to be confirmed by the user before it is run
+struct Confirmation {
+	prompt: String,
+	action: ConfirmAction,
+}
+
+enum ConfirmAction {
+	DeleteText,
+	DeleteConversation(String),
+	SendFiles(Vec<String>),
+	// like `SendFiles`, but the files are deleted afterwards
+	SendPasted(Vec<String>),
+}
+
+impl ConfirmAction {
+	fn cancel(self) {
+		// clean up after anything that was made just for this action
+		if let ConfirmAction::SendPasted(files) = self {
+			for file in files.iter() {
+				let _ = std::fs::remove_file(file);
+			}
+		}
+	}
+}
+
 #[derive(PartialEq, Debug)]
 pub enum AwaitState {
 	More,
@@ -0,0 +1,559 @@
+use crate::{utilities::Utilities, *};
+use clipboard::{ClipboardContext, ClipboardProvider};
+use sdk::{api::APIClient, models::*};
+use std::{
+	cmp::{max, min},
+	collections::HashSet,
+	fs::{create_dir_all, OpenOptions},
+	io::{ErrorKind, Stdout, Write},
+	path::{Path, PathBuf},
+};
+use tokio::sync::RwLock;
+use tui::{
+	layout::Rect,
+	style::{Modifier, Style},
+	terminal::Frame,
+	text::{Span, Spans},
+	widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+pub struct AttachmentsView {
+	pub entries: Vec<AttachmentEntry>,
+	pub selected: usize,
+	pub scroll: u16,
+	pub marked: HashSet<String>,
+	// the type of attachment (e.g. `image`) to show, or all of them if None
+	pub filter: Option<String>,
+	pub client: Arc<RwLock<APIClient>>,
+}
+
+pub struct AttachmentEntry {
+	// the index that it has in the messages view, for `:a`
+	pub index: usize,
+	pub path: String,
+	pub mime_type: String,
+	pub date: i64,
+	pub sender: String,
+}
+
+impl AttachmentsView {
+	pub fn new(client: Arc<RwLock<APIClient>>) -> AttachmentsView {
+		AttachmentsView {
+			entries: Vec::new(),
+			selected: 0,
+			scroll: 0,
+			marked: HashSet::new(),
+			filter: None,
+			client,
+		}
+	}
+
+	pub fn load(&mut self, messages: &[Message]) {
+		// get all the attachments in the messages that are loaded in,
+		// in the same order as they're shown in the messages view
+		self.entries = messages
+			.iter()
+			.flat_map(|m| {
+				let sender = if m.is_from_me {
+					"me".to_owned()
+				} else {
+					m.sender.to_owned().unwrap_or_else(|| "them".to_owned())
+				};
+
+				m.attachments
+					.iter()
+					.map(move |a| (m.date, sender.to_owned(), a))
+			})
+			.enumerate()
+			.map(|(index, (date, sender, att))| AttachmentEntry {
+				index,
+				path: att.path.to_owned(),
+				mime_type: att.mime_type.to_owned(),
+				date,
+				sender,
+			})
+			.collect();
+
+		// start at the newest attachment, like the messages view does
+		self.marked.clear();
+		self.filter = None;
+		self.selected = self.entries.len().saturating_sub(1);
+		self.scroll = 0;
+	}
+
+	pub fn draw_view(
+		&mut self,
+		frame: &mut Frame<CrosstermBackend<Stdout>>,
+		rect: Rect,
+		settings: &Settings,
+	) {
+		let colorscheme = &settings.colorscheme;
+		let inner_height = rect.height.saturating_sub(2);
+
+		// make sure the selected attachment is showing
+		if (self.selected as u16) < self.scroll {
+			self.scroll = self.selected as u16;
+		} else if self.selected as u16 >= self.scroll + inner_height {
+			self.scroll = self.selected as u16 + 1 - inner_height;
+		}
+
+		let sizes = read_state!().attachment_sizes.clone();
+
+		let item_list: Vec<Spans> = self
+			.shown()
+			.iter()
+			.enumerate()
+			.map(|(i, e)| {
+				// like ` [x]  3  image/jpeg   1.2 MB  04/12/2021 13:45  me  IMG_0001.jpeg`
+				let line = format!(
+					"{}{} {:>3}  {:<20} {:>8}  {}  {:<12} {}",
+					if i == self.selected {
+						settings.current_chat_indicator
+					} else {
+						' '
+					},
+					if self.marked.contains(&e.path) {
+						"[x]"
+					} else {
+						"[ ]"
+					},
+					e.index,
+					Utilities::truncate(&e.mime_type, 20),
+					sizes
+						.get(&e.path)
+						.map(|s| Utilities::format_size(*s))
+						.unwrap_or_else(|| "-".to_owned()),
+					Utilities::format_date(e.date),
+					Utilities::truncate(&e.sender, 12),
+					e.path.rsplit('/').next().unwrap_or_default()
+				);
+
+				let style = if i == self.selected {
+					Style::default()
+						.fg(colorscheme.text_color)
+						.add_modifier(Modifier::BOLD)
+				} else {
+					Style::default().fg(colorscheme.text_color)
+				};
+
+				Spans::from(vec![Span::styled(line, style)])
+			})
+			.collect();
+
+		let title = match self.filter {
+			Some(ref filter) => format!("| attachments: {} |", filter),
+			None => "| attachments |".to_owned(),
+		};
+
+		let border = Block::default()
+			.borders(Borders::ALL)
+			.title(title)
+			.border_type(BorderType::Rounded)
+			.border_style(Style::default().fg(colorscheme.selected_box));
+
+		let widget = Paragraph::new(item_list)
+			.block(border)
+			.scroll((self.scroll, 0));
+
+		frame.render_widget(widget, rect);
+	}
+
+	fn shown(&self) -> Vec<&AttachmentEntry> {
+		// the attachments that match the current filter
+		self.entries
+			.iter()
+			.filter(|e| match self.filter {
+				Some(ref filter) => AttachmentsView::kind(&e.mime_type) == filter,
+				None => true,
+			})
+			.collect()
+	}
+
+	fn kind(mime_type: &str) -> &str {
+		// e.g. `image` for `image/jpeg`
+		mime_type.split('/').next().unwrap_or_default()
+	}
+
+	fn current(&self) -> Option<&AttachmentEntry> {
+		self.shown().get(self.selected).copied()
+	}
+
+	pub fn scroll(&mut self, up: bool, distance: u16) {
+		let len = self.shown().len();
+
+		self.selected = if up {
+			max(self.selected as i32 - distance as i32, 0) as usize
+		} else {
+			min(self.selected + distance as usize, len.saturating_sub(1))
+		};
+	}
+
+	pub fn cycle_filter(&mut self) {
+		// go through each type of attachment in the conversation,
+		// and then back to showing all of them
+		let mut kinds = self
+			.entries
+			.iter()
+			.map(|e| AttachmentsView::kind(&e.mime_type).to_owned())
+			.collect::<Vec<String>>();
+
+		kinds.sort();
+		kinds.dedup();
+
+		let next = match self.filter {
+			None => kinds.first(),
+			Some(ref filter) => kinds.iter().skip_while(|k| *k != filter).nth(1),
+		};
+
+		self.filter = next.cloned();
+		self.selected = self.shown().len().saturating_sub(1);
+
+		hint!(
+			"showing {} attachments",
+			self.filter.as_deref().unwrap_or("all")
+		);
+	}
+
+	pub fn toggle_mark(&mut self) {
+		let path = match self.current() {
+			Some(entry) => entry.path.to_owned(),
+			None => return,
+		};
+
+		if !self.marked.remove(&path) {
+			self.marked.insert(path);
+		}
+
+		// move down so that you can quickly mark a bunch in a row
+		self.scroll(false, 1);
+	}
+
+	pub fn download_marked(&mut self, dir: PathBuf, settings: &Settings) {
+		// download all the marked attachments, or the selected one if none are marked
+		let paths = if self.marked.is_empty() {
+			self.current()
+				.map(|e| vec![e.path.to_owned()])
+				.unwrap_or_default()
+		} else {
+			self.entries
+				.iter()
+				.filter(|e| self.marked.contains(&e.path))
+				.map(|e| e.path.to_owned())
+				.collect()
+		};
+
+		if paths.is_empty() {
+			hint!("there are no attachments to download");
+			return;
+		}
+
+		let open_with = if settings.open_downloads {
+			Some(settings.link_opener.to_owned())
+		} else {
+			None
+		};
+
+		AttachmentsView::download(self.client.clone(), paths, dir, open_with);
+
+		self.marked.clear();
+	}
+
+	pub fn open_current(&self, settings: &Settings) {
+		// download the attachment into our cache (or use the copy that's
+		// already there), then open it with whatever opens that kind of file
+		let entry = match self.current() {
+			Some(entry) => entry,
+			None => return,
+		};
+
+		let client = self.client.clone();
+		let path = entry.path.to_owned();
+		let opener = settings.link_opener.to_owned();
+
+		tokio::spawn(async move {
+			match AttachmentsView::cached_copy(&client, &path).await {
+				Ok(loc) => Utilities::open_with_system(&loc.to_string_lossy(), &opener),
+				Err(err) => hint!("could not open {}: {}", path, err),
+			}
+		});
+	}
+
+	async fn cached_copy(client: &Arc<RwLock<APIClient>>, path: &str) -> anyhow::Result<PathBuf> {
+		// attachments that are opened are kept in the cache, in a directory that
+		// only they can read, so opening the same one again doesn't download it again
+		let mut loc = cache_dir();
+		loc.push("attachments");
+		create_dir_all(&loc)?;
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			std::fs::set_permissions(&loc, std::fs::Permissions::from_mode(0o700))?;
+		}
+
+		loc.push(Utilities::cache_file_name(path));
+
+		// it's only written to its real name once it's all there, so if it
+		// exists, it's whole. But check the size too, if we know it
+		let expected = read_state!().attachment_sizes.get(path).copied();
+
+		if let Ok(meta) = std::fs::metadata(&loc) {
+			if expected.map(|s| s as u64 == meta.len()).unwrap_or(true) {
+				return Ok(loc);
+			}
+		}
+
+		hint!("downloading {}...", path.rsplit('/').next().unwrap_or(path));
+
+		let mut api = client.write().await;
+		let data = api.get_attachment(path).await?;
+		drop(api);
+
+		let mut part = loc.clone().into_os_string();
+		part.push(".part");
+
+		std::fs::write(&part, &data)?;
+		std::fs::rename(&part, &loc)?;
+
+		if let Ok(mut state) = STATE.write() {
+			state.attachment_sizes.insert(path.to_owned(), data.len());
+		}
+
+		Ok(loc)
+	}
+
+	pub fn copy_current_path(&self) {
+		let entry = match self.current() {
+			Some(entry) => entry,
+			None => return,
+		};
+
+		let mut ctx: ClipboardContext = match ClipboardProvider::new() {
+			Ok(ctx) => ctx,
+			Err(err) => {
+				hint!("could not copy to clipboard: {}", err);
+				return;
+			}
+		};
+
+		match ctx.set_contents(entry.path.to_owned()) {
+			Err(err) => hint!("could not copy to clipboard: {}", err),
+			Ok(_) => hint!("copied path to clipboard :)"),
+		}
+	}
+
+	pub fn download_dir(settings: &Settings, chat_id: &str, chat_name: &str) -> PathBuf {
+		// where attachments from this chat should be downloaded to;
+		// their download directory, unless they've set a different one,
+		// then the subdirectory for this chat if they've set one
+		// some setups (like services) don't have a home directory at all, so
+		// the temp directory is used instead of giving up
+		let home = || dirs::home_dir().unwrap_or_else(std::env::temp_dir);
+
+		let mut dir = if settings.download_dir.is_empty() {
+			dirs::download_dir().unwrap_or_else(|| {
+				let mut dir = home();
+				dir.push("Downloads");
+				dir
+			})
+		} else if let Some(rest) = settings.download_dir.strip_prefix('~') {
+			let mut dir = home();
+			dir.push(rest.trim_start_matches(['/', '\\']));
+			dir
+		} else {
+			PathBuf::from(&settings.download_dir)
+		};
+
+		// names can have all sorts of characters that can't be in paths, and
+		// they shouldn't be able to point outside of the download directory
+		// (like a chat named `..`), so leading dots are taken off too
+		let clean = |s: &str| {
+			let cleaned = s
+				.chars()
+				.map(|c| {
+					if "/\\:*?\"<>|".contains(c) || c.is_control() {
+						'_'
+					} else {
+						c
+					}
+				})
+				.collect::<String>()
+				.trim()
+				.trim_start_matches('.')
+				.trim()
+				.to_owned();
+
+			if cleaned.is_empty() {
+				"_".to_owned()
+			} else {
+				cleaned
+			}
+		};
+
+		let subdir = settings
+			.download_subdir
+			.replace("{chat}", &clean(chat_id))
+			.replace("{name}", &clean(chat_name));
+
+		if !subdir.trim().is_empty() {
+			dir.push(subdir.trim());
+		}
+
+		dir
+	}
+
+	pub fn download(
+		client: Arc<RwLock<APIClient>>,
+		paths: Vec<String>,
+		dir: PathBuf,
+		open_with: Option<String>,
+	) {
+		// download the attachments at `paths` on the host into `dir`, one at
+		// a time so that we can show how far along it is, and then open
+		// them if they gave something to open them with
+		let total = paths.len();
+
+		tokio::spawn(async move {
+			let mut downloaded = Vec::new();
+			let mut bytes = 0;
+
+			for (i, path) in paths.into_iter().enumerate() {
+				let name = match path.rsplit('/').next() {
+					Some(name) if !name.is_empty() => name.to_owned(),
+					_ => {
+						hint!("cannot get the name of the attachment to download :(");
+						continue;
+					}
+				};
+
+				// the server only sends whole files, so this is as close to
+				// a progress bar as we can get
+				if total > 1 {
+					hint!(
+						"{} downloading {} of {} ({}, {} so far)...",
+						AttachmentsView::progress_bar(i, total),
+						i + 1,
+						total,
+						name,
+						Utilities::format_size(bytes)
+					);
+				} else {
+					hint!("downloading {}...", name);
+				}
+
+				let mut api = client.write().await;
+				let res = api.get_attachment(&path).await;
+				drop(api);
+
+				let data = match res {
+					Ok(data) => data,
+					Err(err) => {
+						hint!("could not download {}: {}", name, err);
+						continue;
+					}
+				};
+
+				match AttachmentsView::write_new_file(&dir, &name, &data) {
+					Ok(loc) => downloaded.push(loc.to_string_lossy().to_string()),
+					Err(err) => {
+						hint!("Could not write {} to {}: {}", name, dir.display(), err);
+						continue;
+					}
+				}
+
+				bytes += data.len();
+
+				if let Ok(mut state) = STATE.write() {
+					state.attachment_sizes.insert(path, data.len());
+				}
+			}
+
+			match (downloaded.len(), open_with) {
+				(0, _) => (),
+				(_, Some(opener)) => {
+					for loc in downloaded.iter() {
+						Utilities::open_with_system(loc, &opener);
+					}
+				}
+				(1, None) => hint!("downloaded file to {} :)", downloaded[0]),
+				(n, None) => hint!(
+					"downloaded {} of {} files to {} :)",
+					n,
+					total,
+					dir.display()
+				),
+			}
+		});
+	}
+
+	fn progress_bar(done: usize, total: usize) -> String {
+		// e.g. `[■■■■□□□□□□]` when 2 out of 5 are done
+		let width = 10;
+		let filled = (done * width).checked_div(total).unwrap_or(0);
+
+		format!("[{}{}]", "■".repeat(filled), "□".repeat(width - filled))
+	}
+
+	fn write_new_file(dir: &Path, name: &str, data: &[u8]) -> std::io::Result<PathBuf> {
+		// write the file without overwriting anything that's already there;
+		// if `name.jpg` exists, it's written to `name (1).jpg`, and so on
+		create_dir_all(dir)?;
+
+		let (stem, ext) = match name.rsplit_once('.') {
+			Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+			_ => (name, "".to_owned()),
+		};
+
+		let mut count = 0;
+
+		loop {
+			let mut loc = dir.to_path_buf();
+
+			if count == 0 {
+				loc.push(name);
+			} else {
+				loc.push(format!("{} ({}){}", stem, count, ext));
+			}
+
+			// `create_new` fails if the file exists, so nothing can sneak in
+			// between checking if it exists and writing to it
+			match OpenOptions::new().write(true).create_new(true).open(&loc) {
+				Ok(mut file) => {
+					file.write_all(data)?;
+					return Ok(loc);
+				}
+				Err(err) if err.kind() == ErrorKind::AlreadyExists => count += 1,
+				Err(err) => return Err(err),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn download_dir_stays_inside_the_download_dir() {
+		let mut settings = Settings::default();
+		settings.download_dir = "/downloads".to_owned();
+		settings.download_subdir = "{name}".to_owned();
+
+		let dir = |name: &str| AttachmentsView::download_dir(&settings, "chat1", name);
+
+		assert_eq!(dir("John Smith"), PathBuf::from("/downloads/John Smith"));
+		assert_eq!(dir(".."), PathBuf::from("/downloads/_"));
+		assert_eq!(dir("../../etc"), PathBuf::from("/downloads/_.._etc"));
+		assert_eq!(dir("a/b\\c"), PathBuf::from("/downloads/a_b_c"));
+		assert_eq!(dir(" .hidden "), PathBuf::from("/downloads/hidden"));
+		assert_eq!(dir(""), PathBuf::from("/downloads/_"));
+	}
+
+	#[test]
+	fn progress_bar_fills_up() {
+		assert_eq!(AttachmentsView::progress_bar(0, 5), "[□□□□□□□□□□]");
+		assert_eq!(AttachmentsView::progress_bar(2, 5), "[■■■■□□□□□□]");
+		assert_eq!(AttachmentsView::progress_bar(5, 5), "[■■■■■■■■■■]");
+		assert_eq!(AttachmentsView::progress_bar(0, 0), "[□□□□□□□□□□]");
+	}
+}
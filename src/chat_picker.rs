@@ -0,0 +1,368 @@
+use crate::{utilities::Utilities, *};
+use crossterm::event::{KeyCode, KeyModifiers};
+use sdk::models::Conversation;
+use std::{
+	cmp::{max, min, Reverse},
+	io::Stdout,
+};
+use tui::{
+	layout::Rect,
+	style::{Modifier, Style},
+	terminal::Frame,
+	text::{Span, Spans},
+	widgets::{Block, BorderType, Borders, Clear, Paragraph},
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+pub struct ChatPicker {
+	pub query: String,
+	// the identifiers of the chats that match, best match first, along with
+	// which chars of their display names matched, to highlight them. These
+	// aren't indices since chats move around when texts come in
+	pub results: Vec<(String, Vec<usize>)>,
+	pub selected: usize,
+}
+
+pub enum PickerAction {
+	Open(usize),
+	Close,
+	Nothing,
+}
+
+impl ChatPicker {
+	pub fn new(query: &str, chats: &[Conversation]) -> ChatPicker {
+		let mut picker = ChatPicker {
+			query: query.to_owned(),
+			results: Vec::new(),
+			selected: 0,
+		};
+
+		picker.rank(chats);
+		picker
+	}
+
+	pub fn rank(&mut self, chats: &[Conversation]) {
+		// score every chat against what they've typed. If two score the same,
+		// the more recent one (the one higher in the chats list) wins, since
+		// sorting is stable.
+		let mut scored = chats
+			.iter()
+			.enumerate()
+			.filter_map(|(i, c)| {
+				let (score, matched) = ChatPicker::score_chat(&self.query, c)?;
+				Some((score, i, matched))
+			})
+			.collect::<Vec<(i64, usize, Vec<usize>)>>();
+
+		scored.sort_by_key(|(score, _, _)| Reverse(*score));
+
+		self.results = scored
+			.into_iter()
+			.map(|(_, i, m)| (chats[i].chat_identifier.to_owned(), m))
+			.collect();
+		self.selected = 0;
+	}
+
+	pub fn best_match(query: &str, chats: &[Conversation]) -> Option<usize> {
+		// for `:c <name>`, if there's only one chat it could mean, just open that
+		let picker = ChatPicker::new(query, chats);
+		match picker.results.len() {
+			1 => ChatPicker::index_of(&picker.results[0].0, chats),
+			_ => None,
+		}
+	}
+
+	fn index_of(id: &str, chats: &[Conversation]) -> Option<usize> {
+		chats.iter().position(|c| c.chat_identifier == id)
+	}
+
+	fn score_chat(query: &str, chat: &Conversation) -> Option<(i64, Vec<usize>)> {
+		// they could be looking for the name, the identifier, or someone in the chat.
+		// The name is what's shown, so that gets a little boost
+		let name = ChatPicker::fuzzy_score(query, &chat.display_name).map(|(s, m)| (s + 1, m));
+
+		let others = [&chat.chat_identifier, &chat.addresses]
+			.iter()
+			.filter_map(|t| ChatPicker::fuzzy_score(query, t))
+			.map(|(s, _)| (s, Vec::new()))
+			.max_by_key(|(s, _)| *s);
+
+		match (name, others) {
+			(Some(name), Some(other)) if other.0 > name.0 => Some(other),
+			(Some(name), _) => Some(name),
+			(None, other) => other,
+		}
+	}
+
+	pub fn fuzzy_score(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+		// kinda like fzf: every char of the query has to show up in the text, in
+		// order, but not necessarily right next to each other. Matches that are
+		// next to each other or at the start of words are worth more, and gaps
+		// between them cost a bit. Returns the score and which graphemes matched.
+		let query = query
+			.to_lowercase()
+			.chars()
+			.filter(|c| !c.is_whitespace())
+			.collect::<Vec<char>>();
+
+		if query.is_empty() {
+			return Some((0, Vec::new()));
+		}
+
+		let graphemes = text.graphemes(true).collect::<Vec<&str>>();
+		let lower = graphemes
+			.iter()
+			.map(|g| g.to_lowercase())
+			.collect::<Vec<String>>();
+
+		let mut score = 0;
+		let mut matched = Vec::new();
+		let mut q = 0;
+
+		for (i, g) in lower.iter().enumerate() {
+			if q == query.len() {
+				break;
+			}
+
+			if !g.starts_with(query[q]) {
+				continue;
+			}
+
+			let word_start = i == 0
+				|| graphemes[i - 1].chars().all(|c| !c.is_alphanumeric())
+				|| (graphemes[i].chars().all(char::is_uppercase)
+					&& graphemes[i - 1].chars().all(char::is_lowercase));
+
+			score += 16;
+
+			if word_start {
+				score += 10;
+			}
+
+			match matched.last() {
+				Some(&last) if last + 1 == i => score += 8,
+				Some(&last) => score -= min(i - last - 1, 10) as i64,
+				None => score -= min(i, 10) as i64 / 2,
+			}
+
+			matched.push(i);
+			q += 1;
+		}
+
+		if q < query.len() {
+			return None;
+		}
+
+		Some((score, matched))
+	}
+
+	pub fn route_key(
+		&mut self,
+		code: KeyCode,
+		modifiers: KeyModifiers,
+		chats: &[Conversation],
+	) -> PickerAction {
+		let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+
+		match code {
+			KeyCode::Esc => return PickerAction::Close,
+			KeyCode::Char('c') | KeyCode::Char('g') if ctrl => return PickerAction::Close,
+			KeyCode::Enter => {
+				// find where the chat is now, in case it's moved since they typed
+				return match self
+					.results
+					.get(self.selected)
+					.and_then(|(id, _)| ChatPicker::index_of(id, chats))
+				{
+					Some(idx) => PickerAction::Open(idx),
+					None => PickerAction::Close,
+				};
+			}
+			// ctrl+p and ctrl+k go up, like in fzf
+			KeyCode::Up | KeyCode::BackTab => self.move_selection(true),
+			KeyCode::Char('p') | KeyCode::Char('k') if ctrl => self.move_selection(true),
+			KeyCode::Down | KeyCode::Tab => self.move_selection(false),
+			KeyCode::Char('n') | KeyCode::Char('j') if ctrl => self.move_selection(false),
+			KeyCode::Char('u') if ctrl => {
+				self.query.clear();
+				self.rank(chats);
+			}
+			KeyCode::Backspace => {
+				self.query.pop();
+				self.rank(chats);
+			}
+			KeyCode::Char(c) if !ctrl => {
+				self.query.push(c);
+				self.rank(chats);
+			}
+			_ => (),
+		}
+
+		PickerAction::Nothing
+	}
+
+	fn move_selection(&mut self, up: bool) {
+		if self.results.is_empty() {
+			return;
+		}
+
+		self.selected = if up {
+			self.selected
+				.checked_sub(1)
+				.unwrap_or(self.results.len() - 1)
+		} else {
+			(self.selected + 1) % self.results.len()
+		};
+	}
+
+	pub fn draw_view(
+		&self,
+		frame: &mut Frame<CrosstermBackend<Stdout>>,
+		rect: Rect,
+		chats: &[Conversation],
+		settings: &Settings,
+	) {
+		let colorscheme = &settings.colorscheme;
+
+		let width = min(max(rect.width * 2 / 3, 40), rect.width);
+		let height = min(max(rect.height * 2 / 3, 8), rect.height);
+		let popup = Utilities::centered_rect(width, height, rect);
+
+		// one line for the query, and the rest for the chats
+		let list_height = popup.height.saturating_sub(3) as usize;
+		let first = self.selected.saturating_sub(list_height.saturating_sub(1));
+
+		let mut lines = vec![Spans::from(vec![
+			Span::styled("> ", Style::default().fg(colorscheme.chat_indicator)),
+			Span::styled(
+				self.query.as_str(),
+				Style::default().fg(colorscheme.text_color),
+			),
+			Span::styled(
+				format!("  {}/{}", self.results.len(), chats.len()),
+				Style::default().fg(colorscheme.hints_box),
+			),
+		])];
+
+		lines.extend(
+			self.results
+				.iter()
+				.enumerate()
+				.skip(first)
+				.take(list_height)
+				.filter_map(|(i, (id, matched))| {
+					let idx = ChatPicker::index_of(id, chats)?;
+					let chat = &chats[idx];
+					let selected = i == self.selected;
+
+					let base = if selected {
+						Style::default()
+							.fg(colorscheme.text_color)
+							.add_modifier(Modifier::BOLD)
+					} else {
+						Style::default().fg(colorscheme.text_color)
+					};
+
+					let mut spans = vec![Span::styled(
+						format!(
+							"{}{:>3} ",
+							if selected {
+								settings.current_chat_indicator
+							} else {
+								' '
+							},
+							idx
+						),
+						base,
+					)];
+
+					// highlight the parts of the name that matched what they typed
+					spans.extend(chat.display_name.graphemes(true).enumerate().map(
+						|(g_idx, g)| {
+							let style = if matched.contains(&g_idx) {
+								base.fg(colorscheme.chat_indicator)
+							} else {
+								base
+							};
+							Span::styled(g.to_owned(), style)
+						},
+					));
+
+					// and show the identifier too, if the name isn't the identifier
+					if chat.display_name != chat.chat_identifier {
+						spans.push(Span::styled(
+							format!("  {}", chat.chat_identifier),
+							Style::default().fg(colorscheme.hints_box),
+						));
+					}
+
+					Some(Spans::from(spans))
+				}),
+		);
+
+		let widget = Paragraph::new(lines).block(
+			Block::default()
+				.title("| open chat |")
+				.borders(Borders::ALL)
+				.border_type(BorderType::Rounded)
+				.border_style(Style::default().fg(colorscheme.selected_box)),
+		);
+
+		frame.render_widget(Clear, popup);
+		frame.render_widget(widget, popup);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn score(query: &str, text: &str) -> Option<i64> {
+		ChatPicker::fuzzy_score(query, text).map(|(s, _)| s)
+	}
+
+	#[test]
+	fn fuzzy_score_needs_every_char_in_order() {
+		assert!(score("jsm", "John Smith").is_some());
+		assert!(score("JOHN", "john smith").is_some());
+		assert!(score("j s", "John Smith").is_some());
+
+		assert!(score("msj", "John Smith").is_none());
+		assert!(score("johnx", "John Smith").is_none());
+	}
+
+	#[test]
+	fn fuzzy_score_returns_matched_graphemes() {
+		assert_eq!(
+			ChatPicker::fuzzy_score("js", "John Smith"),
+			Some((score("js", "John Smith").unwrap(), vec![0, 5]))
+		);
+
+		// indices are graphemes, not bytes
+		assert_eq!(ChatPicker::fuzzy_score("b", "👨‍👩‍👧 Bob").unwrap().1, vec![2]);
+	}
+
+	#[test]
+	fn fuzzy_score_empty_query_matches_everything() {
+		assert_eq!(
+			ChatPicker::fuzzy_score("", "anything"),
+			Some((0, Vec::new()))
+		);
+		assert_eq!(ChatPicker::fuzzy_score("  ", ""), Some((0, Vec::new())));
+	}
+
+	#[test]
+	fn fuzzy_score_ranks_better_matches_higher() {
+		// together beats spread out
+		assert!(score("smi", "John Smith") > score("smi", "Sam Mills Inc"));
+
+		// word starts beat the middle of words
+		assert!(score("s", "John Smith") > score("s", "Jess"));
+
+		// camel case counts as the start of a word
+		assert!(score("gc", "GroupChat") > score("gc", "Gracie"));
+
+		// earlier matches beat later ones
+		assert!(score("a", "Anna") > score("a", "Bob and Anna"));
+	}
+}
@@ -1,8 +1,11 @@
-use crate::{app::AwaitState, *};
+use crate::{app::AwaitState, names::NameCache, utilities::Utilities, *};
+use chrono::Local;
 use sdk::{models::*, *};
 use std::{
 	cmp::{max, min, Ordering},
+	collections::{HashMap, HashSet},
 	io::Stdout,
+	path::PathBuf,
 };
 use tokio::sync::RwLock;
 use tui::{
@@ -12,18 +15,42 @@ use tui::{
 	text::{Span, Spans},
 	widgets::{Block, BorderType, Borders, Paragraph},
 };
-use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 pub struct ChatsView {
 	pub scroll: u16,
 	pub chats: Vec<Conversation>,
-	pub chats_list: Vec<String>,
+	pub chats_list: Vec<ChatLine>,
+	// how many lines each chat takes up in the list
+	pub lines_per_chat: u16,
 	pub last_width: u16,
 	pub last_height: u16,
 	pub last_selected: Option<usize>,
 	pub client: Arc<RwLock<APIClient>>,
 	pub await_state: AwaitState,
+	pub drafts: HashMap<String, String>,
+	// what they've typed after `/` to narrow down the chats, if anything
+	pub filter: Option<String>,
+	pub unread_only: bool,
+	pub groups_only: bool,
+	// how many texts have come in for each chat since it was last opened
+	pub unread_counts: HashMap<String, usize>,
+	// chats that they've pinned or unpinned here, since the server can't do it
+	pub pins: HashMap<String, bool>,
+	// when each muted chat should be unmuted (as a unix timestamp), or 0 for never
+	pub muted: HashMap<String, i64>,
+	pub archived: HashSet<String>,
+	// whether the list shows the archived chats instead of the rest of them
+	pub show_archived: bool,
+}
+
+pub struct ChatLine {
+	pub text: String,  // like '  0 > John Smith   '
+	pub badge: String, // the number of unread texts, if there are any
+	pub time: String,
+	pub preview: Option<String>,
+	// shown instead of the blank line above the chat, at the start of each section
+	pub header: Option<&'static str>,
 }
 
 impl ChatsView {
@@ -51,11 +78,21 @@ impl ChatsView {
 		ChatsView {
 			scroll: 0,
 			chats_list: Vec::new(),
+			lines_per_chat: 2,
 			last_width: 0,
 			last_height: 0,
 			last_selected: None,
 			await_state: AwaitState::Replace,
 			chats: Vec::new(),
+			drafts: ChatsView::load_drafts(),
+			filter: None,
+			unread_only: false,
+			groups_only: false,
+			unread_counts: HashMap::new(),
+			pins: ChatsView::load_pins(),
+			muted: ChatsView::load_muted(),
+			archived: ChatsView::load_archived(),
+			show_archived: false,
 			client,
 		}
 	}
@@ -82,7 +119,7 @@ impl ChatsView {
 		// create the list of spans, which are what is printed with `tui`.
 		let item_list: Vec<Spans> = self.chats_list.iter().fold(Vec::new(), |mut s, c| {
 			// that's where the symbol will be
-			let (num, rest) = c.split_at(4);
+			let (num, rest) = c.text.split_at(4);
 			let symbol = rest.chars().next().unwrap_or(' ');
 
 			let rest_style = if symbol == settings.current_chat_indicator {
@@ -94,7 +131,7 @@ impl ChatsView {
 			};
 
 			// conditionally color the symbol and create its span
-			let spans = vec![
+			let mut spans = vec![
 				Span::styled(num, Style::default().fg(colorscheme.text_color)),
 				match symbol {
 					_ if symbol == settings.current_chat_indicator => Span::styled(
@@ -105,21 +142,54 @@ impl ChatsView {
 						symbol.to_string(),
 						Style::default().fg(colorscheme.unread_indicator),
 					),
+					_ if symbol == settings.draft_chat_indicator => Span::styled(
+						symbol.to_string(),
+						Style::default().fg(colorscheme.chat_indicator),
+					),
 					_ => Span::raw(" "),
 				},
 				Span::styled(rest.replacen(symbol, "", 1), rest_style),
 			];
 
-			// add spacing and line of text
-			s.push(Spans::from(vec![Span::raw("")]));
+			// the unread count and time go on the right side of the first line
+			if !c.badge.is_empty() {
+				spans.push(Span::styled(
+					format!(" {}", c.badge),
+					Style::default()
+						.fg(colorscheme.unread_badge)
+						.add_modifier(tui::style::Modifier::BOLD),
+				));
+			}
+
+			if !c.time.is_empty() {
+				spans.push(Span::styled(
+					format!(" {}", c.time),
+					Style::default().fg(colorscheme.chat_preview),
+				));
+			}
+
+			// add spacing (or the name of the section) and line of text
+			s.push(Spans::from(vec![Span::styled(
+				c.header.unwrap_or(""),
+				Style::default().fg(colorscheme.hints_box),
+			)]));
 			s.push(Spans::from(spans));
+
+			// and the latest text goes under it, lined up with the name
+			if let Some(ref preview) = c.preview {
+				s.push(Spans::from(vec![Span::styled(
+					format!("      {}", preview),
+					Style::default().fg(colorscheme.chat_preview),
+				)]));
+			}
+
 			s
 		});
 
 		// create the border for the view
 		let chats_border = Block::default()
 			.borders(Borders::ALL)
-			.title(settings.chats_title.as_str())
+			.title(self.title(settings))
 			.border_type(BorderType::Rounded)
 			.border_style(Style::default().fg(if is_selected {
 				colorscheme.selected_box
@@ -130,53 +200,90 @@ impl ChatsView {
 		// create the actual view that will be printed
 		let chats_widget = Paragraph::new(item_list)
 			.block(chats_border)
-			.scroll((self.scroll * 2, 0));
+			.scroll((self.scroll * self.lines_per_chat, 0));
 
 		// render it!
 		frame.render_widget(chats_widget, rect);
 	}
 
 	pub fn rerender_list(&mut self, rect: Rect, settings: &Settings) {
-		let max_len: usize = (rect.width as u64 - 8) as usize;
+		// the index, the symbol, and the spaces around them take up 6 columns
+		let max_len = (rect.width as usize).saturating_sub(8);
 
-		// iterate over all of them and create the list of strings
-		// that will be printed
-		self.chats_list = self
+		// each chat has a blank line above it, and the preview under it if they want
+		self.lines_per_chat = if settings.chat_previews {
+			3
+		} else {
+			2
+		};
+
+		// pinned chats go in their own section at the top, no matter where they are
+		// in the list. They keep their indices, like the ones that are filtered
+		// out, so that `:c` still opens the chat that they see
+		let (pinned, others): (Vec<_>, Vec<_>) = self
 			.chats
 			.iter()
 			.enumerate()
-			.map(|(i, c)| {
+			.filter(|(_, c)| self.shows(c))
+			.partition(|(_, c)| self.is_pinned(c));
+
+		let has_pinned = !pinned.is_empty();
+		let first_other = others.first().map(|(i, _)| *i);
+
+		// iterate over all of them and create the list of strings
+		// that will be printed
+		self.chats_list = pinned
+			.into_iter()
+			.chain(others)
+			.enumerate()
+			.map(|(pos, (i, c))| {
+				let header = match (has_pinned, pos == 0, Some(i) == first_other) {
+					(true, true, _) => Some(" pinned"),
+					(true, _, true) => Some(" everything else"),
+					_ => None,
+				};
+
 				// get symbol for the chat that will represent whether
 				// it has an unread message, is selected, or neither.
 				let symbol = if c.is_selected {
 					settings.current_chat_indicator
 				} else if c.has_unread {
 					settings.unread_chat_indicator
+				} else if self.drafts.contains_key(&c.chat_identifier) {
+					settings.draft_chat_indicator
 				} else {
 					' '
 				};
 
-				let display_len = UnicodeWidthStr::width(c.display_name.as_str());
+				let badge = match self.unread_counts.get(&c.chat_identifier) {
+					Some(count) if settings.unread_counts && c.has_unread => count.to_string(),
+					_ => "".to_owned(),
+				};
 
-				// only show what part of the name will fit, with ellipsis.
-				let name = if display_len > max_len {
-					let graphemes = c.display_name.graphemes(true).collect::<Vec<&str>>();
+				let time = if settings.chat_previews {
+					c.relative_time.to_owned()
+				} else {
+					"".to_owned()
+				};
 
-					let num_graphemes = graphemes.iter().fold(0, |n, g| {
-						if n >= max_len - 3 {
-							return n;
-						}
-						let total = UnicodeWidthStr::width(*g) + n;
-						if total > max_len - 3 {
-							n
-						} else {
-							n + 1
-						}
-					});
+				// the badge and time are right-aligned, so the name gets whatever's left
+				let right_len = [&badge, &time]
+					.iter()
+					.filter(|s| !s.is_empty())
+					.map(|s| UnicodeWidthStr::width(s.as_str()) + 1)
+					.sum::<usize>();
+				let name_len = max_len.saturating_sub(right_len);
 
-					format!("{}...", &graphemes[..num_graphemes].join(""))
+				// only show what part of the name will fit, with ellipsis.
+				let name = Utilities::truncate(&c.display_name, name_len);
+				let padding = name_len.saturating_sub(UnicodeWidthStr::width(name.as_str()));
+
+				// texts can have new lines in them, but there's only one line for the preview
+				let preview = if settings.chat_previews {
+					let flat = c.latest_text.split_whitespace().collect::<Vec<&str>>();
+					Some(Utilities::truncate(&flat.join(" "), max_len))
 				} else {
-					c.display_name.to_owned()
+					None
 				};
 
 				// index; number that they will have to use to select the chat
@@ -197,7 +304,13 @@ impl ChatsView {
 				// I'm just gonna hope that nobody is going 1000 chats deep :/
 
 				// like '  0 > John Smith         '
-				format!("{} {} {}", idx, symbol, name)
+				ChatLine {
+					text: format!("{} {} {}{}", idx, symbol, name, " ".repeat(padding)),
+					badge,
+					time,
+					preview,
+					header,
+				}
 			})
 			.collect();
 	}
@@ -206,7 +319,8 @@ impl ChatsView {
 		// allow people to scroll multiple lines at once
 		if !up {
 			// only scroll to lower limit
-			let max = self.chats_list.len() as u16 - (self.last_height / 2) + 2;
+			let max = (self.chats_list.len() as u16 + 2)
+				.saturating_sub(self.last_height / self.lines_per_chat);
 			self.scroll = min(self.scroll + distance, max);
 
 			// load in new texts automatically if you hit the limit
@@ -239,6 +353,267 @@ impl ChatsView {
 		}
 	}
 
+	fn shows(&self, chat: &Conversation) -> bool {
+		// whether or not this chat makes it through the filters. The open chat
+		// always shows when only showing unread chats, since opening it reads it
+		if self.archived.contains(&chat.chat_identifier) != self.show_archived {
+			return false;
+		}
+
+		if self.unread_only && !chat.has_unread && !chat.is_selected {
+			return false;
+		}
+
+		// group chats have identifiers like `chat1234...`, and more than one address
+		if self.groups_only
+			&& !chat.chat_identifier.starts_with("chat")
+			&& !chat.addresses.contains(',')
+		{
+			return false;
+		}
+
+		match self.filter {
+			Some(ref filter) => {
+				let filter = filter.to_lowercase();
+
+				[&chat.display_name, &chat.addresses, &chat.latest_text]
+					.iter()
+					.any(|t| t.to_lowercase().contains(&filter))
+			}
+			None => true,
+		}
+	}
+
+	fn title(&self, settings: &Settings) -> String {
+		// show what it's being filtered by, so that they know why chats are missing
+		let mut filters = Vec::new();
+
+		if let Some(ref filter) = self.filter {
+			filters.push(format!("/{}", filter));
+		}
+		if self.show_archived {
+			filters.push("archived".to_owned());
+		}
+		if self.unread_only {
+			filters.push("unread".to_owned());
+		}
+		if self.groups_only {
+			filters.push("groups".to_owned());
+		}
+
+		if filters.is_empty() {
+			settings.chats_title.to_owned()
+		} else {
+			format!("{} {} |", settings.chats_title, filters.join(", "))
+		}
+	}
+
+	pub fn set_filter(&mut self, filter: Option<&str>) {
+		let filter = filter.filter(|f| !f.is_empty()).map(|f| f.to_owned());
+
+		if filter != self.filter {
+			self.filter = filter;
+			self.filters_changed();
+		}
+	}
+
+	pub fn toggle_unread_only(&mut self) {
+		self.unread_only = !self.unread_only;
+		self.filters_changed();
+	}
+
+	pub fn toggle_groups_only(&mut self) {
+		self.groups_only = !self.groups_only;
+		self.filters_changed();
+	}
+
+	pub fn toggle_archived(&mut self) {
+		self.show_archived = !self.show_archived;
+		self.filters_changed();
+	}
+
+	pub fn clear_filters(&mut self) {
+		self.show_archived = false;
+		self.filter = None;
+		self.unread_only = false;
+		self.groups_only = false;
+		self.filters_changed();
+	}
+
+	pub fn is_filtered(&self) -> bool {
+		self.filter.is_some() || self.unread_only || self.groups_only || self.show_archived
+	}
+
+	fn filters_changed(&mut self) {
+		// the list could be a lot shorter now, so go back to the top of it
+		self.scroll = 0;
+		self.last_height = 0;
+	}
+
+	pub fn set_draft(&mut self, chat: &str, draft: &str) {
+		// save a half-written text for a chat, or get rid of it if there's nothing there
+		if draft.trim().is_empty() {
+			self.drafts.remove(chat);
+		} else {
+			self.drafts.insert(chat.to_owned(), draft.to_owned());
+		}
+
+		self.save_drafts();
+	}
+
+	pub fn take_draft(&mut self, chat: &str) -> Option<String> {
+		let draft = self.drafts.remove(chat)?;
+		self.save_drafts();
+		Some(draft)
+	}
+
+	fn load_drafts() -> HashMap<String, String> {
+		// drafts are kept between sessions, so that they don't get lost if they quit
+		let table = match Utilities::read_toml(&ChatsView::drafts_file()) {
+			Some(table) => table,
+			None => return HashMap::new(),
+		};
+
+		table
+			.into_iter()
+			.filter_map(|(chat, draft)| Some((chat, draft.as_str()?.to_owned())))
+			.collect()
+	}
+
+	fn save_drafts(&mut self) {
+		let table = self
+			.drafts
+			.iter()
+			.map(|(chat, draft)| (chat.to_owned(), draft.to_owned().into()))
+			.collect();
+
+		Utilities::write_toml(&ChatsView::drafts_file(), table);
+
+		// so that the draft indicators are redrawn
+		self.last_height = 0;
+	}
+
+	pub fn is_pinned(&self, chat: &Conversation) -> bool {
+		*self.pins.get(&chat.chat_identifier).unwrap_or(&chat.pinned)
+	}
+
+	pub fn set_pinned(&mut self, idx: usize, pinned: bool) {
+		// the server can't pin chats, so they're only pinned in here. If it's
+		// back to what the server says, there's no need to remember it
+		let chat = &self.chats[idx];
+
+		if chat.pinned == pinned {
+			self.pins.remove(&chat.chat_identifier);
+		} else {
+			self.pins.insert(chat.chat_identifier.to_owned(), pinned);
+		}
+
+		let table = self
+			.pins
+			.iter()
+			.map(|(chat, pinned)| (chat.to_owned(), (*pinned).into()))
+			.collect();
+
+		Utilities::write_toml(&ChatsView::pins_file(), table);
+
+		self.last_height = 0;
+	}
+
+	fn load_pins() -> HashMap<String, bool> {
+		let table = match Utilities::read_toml(&ChatsView::pins_file()) {
+			Some(table) => table,
+			None => return HashMap::new(),
+		};
+
+		table
+			.into_iter()
+			.filter_map(|(chat, pinned)| Some((chat, pinned.as_bool()?)))
+			.collect()
+	}
+
+	pub fn is_muted(&self, chat: &str) -> bool {
+		match self.muted.get(chat) {
+			Some(0) => true,
+			Some(until) => *until > Local::now().timestamp(),
+			None => false,
+		}
+	}
+
+	pub fn set_muted(&mut self, chat: &str, until: Option<i64>) {
+		// `None` unmutes it, and `Some(0)` mutes it until they unmute it
+		match until {
+			Some(until) => self.muted.insert(chat.to_owned(), until),
+			None => self.muted.remove(chat),
+		};
+
+		// there's no need to keep the ones that have run out
+		let now = Local::now().timestamp();
+		self.muted.retain(|_, until| *until == 0 || *until > now);
+
+		let table = self
+			.muted
+			.iter()
+			.map(|(chat, until)| (chat.to_owned(), (*until).into()))
+			.collect();
+
+		Utilities::write_toml(&ChatsView::flags_file("muted"), table);
+	}
+
+	pub fn set_archived(&mut self, chat: &str, archived: bool) {
+		if archived {
+			self.archived.insert(chat.to_owned());
+		} else {
+			self.archived.remove(chat);
+		}
+
+		let table = self
+			.archived
+			.iter()
+			.map(|chat| (chat.to_owned(), true.into()))
+			.collect();
+
+		Utilities::write_toml(&ChatsView::flags_file("archived"), table);
+
+		self.last_height = 0;
+	}
+
+	fn load_muted() -> HashMap<String, i64> {
+		let table = match Utilities::read_toml(&ChatsView::flags_file("muted")) {
+			Some(table) => table,
+			None => return HashMap::new(),
+		};
+
+		table
+			.into_iter()
+			.filter_map(|(chat, until)| Some((chat, until.as_integer()?)))
+			.collect()
+	}
+
+	fn load_archived() -> HashSet<String> {
+		match Utilities::read_toml(&ChatsView::flags_file("archived")) {
+			Some(table) => table.into_iter().map(|(chat, _)| chat).collect(),
+			None => HashSet::new(),
+		}
+	}
+
+	fn flags_file(name: &str) -> PathBuf {
+		let mut file = config_dir();
+		file.push(format!("{}.toml", name));
+		file
+	}
+
+	fn pins_file() -> PathBuf {
+		let mut file = config_dir();
+		file.push("pins.toml");
+		file
+	}
+
+	fn drafts_file() -> PathBuf {
+		let mut file = config_dir();
+		file.push("drafts.toml");
+		file
+	}
+
 	pub fn load_in_conversation(&mut self, idx: usize) {
 		// de-select old conversation
 		if let Some(old) = self.last_selected {
@@ -249,6 +624,7 @@ impl ChatsView {
 		let mut chat = &mut self.chats[idx];
 		chat.has_unread = false;
 		chat.is_selected = true;
+		self.unread_counts.remove(&chat.chat_identifier);
 
 		self.last_selected = Some(idx);
 		// kinda dirty trick to force it to redraw the list next time
@@ -261,6 +637,14 @@ impl ChatsView {
 		// Make sure that the new text has a chat identifier -- it should,
 		// if it came through the WebSocket, which it must have.
 		if let Some(id) = &item.chat_identifier {
+			// a new text brings a chat back out of the archive
+			if self.archived.contains(id) {
+				self.set_archived(id, false);
+			}
+
+			// and muted chats don't get marked as unread
+			let muted = self.is_muted(id);
+
 			// check if the conversation already is on the list that is showing.
 			let chat = self.chats.iter().position(|c| c.chat_identifier == *id);
 
@@ -268,10 +652,14 @@ impl ChatsView {
 			if let Some(idx) = chat {
 				// remove it from the list, set to unread.
 				let mut old_chat = self.chats.remove(idx);
-				if !item.is_from_me {
+				if !item.is_from_me && !muted {
 					old_chat.has_unread = true;
 				}
 
+				// so that the preview shows what was just sent
+				old_chat.latest_text = item.text.to_owned();
+				old_chat.relative_time = Local::now().format("%H:%M").to_string();
+
 				// last_selected specifies the conversation whose messages
 				// are currently being viewed
 				if let Some(ls) = self.last_selected {
@@ -295,6 +683,10 @@ impl ChatsView {
 					}
 				}
 
+				if old_chat.has_unread {
+					*self.unread_counts.entry(id.to_owned()).or_insert(0) += 1;
+				}
+
 				// ret will contain the old index of the chat
 				// that contains this conversation
 				ret = chat;
@@ -304,10 +696,7 @@ impl ChatsView {
 			} else {
 				// get the name of the conversation -- it's the only information
 				// we need to create a new Conversation object.
-				//let name = if let Ok(mut api) = self.client.write() {
-				let mut api = self.client.write().await;
-
-				let name = match api.get_name(id).await {
+				let name = match NameCache::get_name(&self.client, id).await {
 					Ok(name) => name,
 					Err(err) => {
 						hint!("Couldn't get name: {}", err);
@@ -315,19 +704,21 @@ impl ChatsView {
 					}
 				};
 
-				drop(api);
-
 				let new_convo = Conversation {
 					display_name: name,
 					chat_identifier: id.to_owned(),
 					latest_text: item.text.to_owned(),
-					has_unread: true,
+					has_unread: !muted,
 					addresses: id.to_owned(),
 					is_selected: false,
 					pinned: false,
-					relative_time: "".to_owned(),
+					relative_time: Local::now().format("%H:%M").to_string(),
 				};
 
+				if !muted {
+					self.unread_counts.insert(id.to_owned(), 1);
+				}
+
 				// Must increase the currently selected index if one is selected,
 				// since this chat won't be on the list.
 				if let Some(ls) = self.last_selected {
@@ -1,3 +1,4 @@
+use crate::utilities::Utilities;
 use std::collections::HashMap;
 use tui::style::Color;
 
@@ -13,9 +14,28 @@ pub struct Colorscheme {
 	pub unread_indicator: Color,
 	pub text_color: Color,
 	pub hints_box: Color,
+	pub link: Color,
+	pub chat_preview: Color,
+	pub unread_badge: Color,
+	// the colors that the senders in group chats are each given one of
+	pub sender_colors: Vec<Color>,
 }
 
 impl Colorscheme {
+	pub fn names(extra: &Option<Vec<Colorscheme>>) -> Vec<String> {
+		// the names of all the colorschemes they can pick from
+		let mut names = ["forest", "rose-pine", "hacker", "dracula"]
+			.iter()
+			.map(|n| n.to_string())
+			.collect::<Vec<String>>();
+
+		if let Some(extra) = extra {
+			names.extend(extra.iter().map(|c| c.name.to_owned()));
+		}
+
+		names
+	}
+
 	pub fn with_name<T: Into<String>>(name: T, extra: &Option<Vec<Colorscheme>>) -> Colorscheme {
 		// yeahhh... ugly. Whatcha gonna do
 
@@ -39,6 +59,9 @@ impl Colorscheme {
 				[235, 111, 146],
 				[224, 222, 244],
 				[112, 110, 134],
+				[156, 207, 216],
+				[144, 140, 170],
+				[235, 111, 146],
 			],
 			"hacker" => [
 				[32, 160, 14],
@@ -50,6 +73,9 @@ impl Colorscheme {
 				[32, 160, 14],
 				[236, 236, 236],
 				[32, 160, 14],
+				[32, 160, 14],
+				[120, 120, 120],
+				[255, 255, 255],
 			],
 			"dracula" => [
 				[139, 233, 253],
@@ -61,6 +87,9 @@ impl Colorscheme {
 				[255, 184, 108],
 				[248, 248, 242],
 				[80, 250, 123],
+				[139, 233, 253],
+				[98, 114, 164],
+				[255, 85, 85],
 			],
 			_ => [
 				// forest
@@ -73,11 +102,13 @@ impl Colorscheme {
 				[245, 111, 66],
 				[255, 255, 255],
 				[195, 137, 138],
+				[101, 215, 253],
+				[150, 150, 150],
+				[245, 111, 66],
 			],
 		};
 
 		Colorscheme {
-			name,
 			selected_box: Color::Rgb(vals[0][0], vals[0][1], vals[0][2]),
 			unselected_box: Color::Rgb(vals[1][0], vals[1][1], vals[1][2]),
 			my_underline: Color::Rgb(vals[2][0], vals[2][1], vals[2][2]),
@@ -87,11 +118,74 @@ impl Colorscheme {
 			unread_indicator: Color::Rgb(vals[6][0], vals[6][1], vals[6][2]),
 			text_color: Color::Rgb(vals[7][0], vals[7][1], vals[7][2]),
 			hints_box: Color::Rgb(vals[8][0], vals[8][1], vals[8][2]),
+			link: Color::Rgb(vals[9][0], vals[9][1], vals[9][2]),
+			chat_preview: Color::Rgb(vals[10][0], vals[10][1], vals[10][2]),
+			unread_badge: Color::Rgb(vals[11][0], vals[11][1], vals[11][2]),
+			sender_colors: Colorscheme::sender_palette(&name),
+			name,
 		}
 	}
 
-	// this does no validation at all. Will panic if anything is off
+	fn sender_palette(name: &str) -> Vec<Color> {
+		let vals: &[[u8; 3]] = match name {
+			"rose-pine" => &[
+				[235, 111, 146],
+				[246, 193, 119],
+				[235, 188, 186],
+				[49, 116, 143],
+				[156, 207, 216],
+				[196, 167, 231],
+			],
+			"hacker" => &[
+				[32, 160, 14],
+				[80, 220, 60],
+				[150, 255, 120],
+				[20, 110, 10],
+				[200, 255, 180],
+				[120, 120, 120],
+			],
+			"dracula" => &[
+				[255, 85, 85],
+				[255, 184, 108],
+				[241, 250, 140],
+				[80, 250, 123],
+				[139, 233, 253],
+				[189, 147, 249],
+				[255, 121, 198],
+			],
+			_ => &[
+				[245, 111, 66],
+				[101, 215, 253],
+				[30, 141, 199],
+				[195, 137, 138],
+				[36, 139, 84],
+				[230, 200, 90],
+			],
+		};
+
+		vals.iter().map(|c| Color::Rgb(c[0], c[1], c[2])).collect()
+	}
+
+	pub fn sender_color(&self, sender: &str) -> Option<Color> {
+		// the same person should always get the same color, so this hashes their
+		// name (with FNV, since the std hasher can change between versions)
+		if self.sender_colors.is_empty() {
+			return None;
+		}
+
+		let hash = Utilities::stable_hash(sender);
+
+		Some(self.sender_colors[(hash % self.sender_colors.len() as u64) as usize])
+	}
+
+	// this does no validation at all. Will panic if anything is off.
+	// Colors that were added after custom colorschemes were introduced
+	// are optional, and fall back to a similar required color.
 	pub fn from_specs(name: String, map: HashMap<String, Vec<u8>>) -> Colorscheme {
+		let li = map.get("link").unwrap_or(&map["text_color"]);
+		let cp = map.get("chat_preview").unwrap_or(&map["hints_box"]);
+		let bd = map.get("unread_badge").unwrap_or(&map["unread_indicator"]);
+
 		let (sb, ub, mu, tu, su, ci, ui, tc, hb) = (
 			&map["selected_box"],
 			&map["unselected_box"],
@@ -104,6 +198,20 @@ impl Colorscheme {
 			&map["hints_box"],
 		);
 
+		// these are all in one array, like [r, g, b, r, g, b, ...]
+		let sender_colors = match map.get("sender_colors") {
+			Some(colors) => colors
+				.chunks_exact(3)
+				.map(|c| Color::Rgb(c[0], c[1], c[2]))
+				.collect(),
+			None => vec![
+				Color::Rgb(tu[0], tu[1], tu[2]),
+				Color::Rgb(mu[0], mu[1], mu[2]),
+				Color::Rgb(ci[0], ci[1], ci[2]),
+				Color::Rgb(ui[0], ui[1], ui[2]),
+			],
+		};
+
 		Colorscheme {
 			name,
 			selected_box: Color::Rgb(sb[0], sb[1], sb[2]),
@@ -115,6 +223,10 @@ impl Colorscheme {
 			unread_indicator: Color::Rgb(ui[0], ui[1], ui[2]),
 			text_color: Color::Rgb(tc[0], tc[1], tc[2]),
 			hints_box: Color::Rgb(hb[0], hb[1], hb[2]),
+			link: Color::Rgb(li[0], li[1], li[2]),
+			chat_preview: Color::Rgb(cp[0], cp[1], cp[2]),
+			unread_badge: Color::Rgb(bd[0], bd[1], bd[2]),
+			sender_colors,
 		}
 	}
 }
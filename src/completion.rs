@@ -0,0 +1,642 @@
+use crate::{app::TAPBACKS, colorscheme::Colorscheme, utilities::Utilities, *};
+use sdk::models::Conversation;
+use std::{
+	cmp::{max, min},
+	io::Stdout,
+	path::PathBuf,
+};
+use tui::{
+	layout::Rect,
+	style::{Modifier, Style},
+	terminal::Frame,
+	text::{Span, Spans},
+	widgets::{Block, BorderType, Borders, Clear, Paragraph},
+};
+use unicode_width::UnicodeWidthStr;
+
+// all the commands, so that they can be completed from just the start of them
+const COMMANDS: [&str; 25] = [
+	":a",
+	":alias",
+	":archive",
+	":b",
+	":c",
+	":dc",
+	":dt",
+	":e",
+	":es",
+	":f",
+	":h",
+	":members",
+	":mute",
+	":n",
+	":o",
+	":paste",
+	":pin",
+	":q",
+	":r",
+	":s",
+	":t",
+	":unarchive",
+	":unmute",
+	":unpin",
+	":y",
+];
+
+// the most candidates that are shown in the popup at once
+const POPUP_HEIGHT: usize = 8;
+
+pub struct Candidate {
+	pub text: String,   // what the word is replaced with
+	pub label: String,  // what's shown in the popup, e.g. the chat's name beside its index
+	pub finished: bool, // if a space should be put after it when it's the only option
+}
+
+pub struct Completion {
+	pub candidates: Vec<Candidate>,
+	pub selected: Option<usize>,
+	pub start: usize, // the byte index in the input where the word being completed starts
+	pub completed: String, // the input after the last completion, so we know if they've typed since
+}
+
+impl Completion {
+	pub fn for_input(
+		input: &str,
+		chats: &[Conversation],
+		settings: &Settings,
+	) -> Option<Completion> {
+		// figure out what the last word of the input could be, based on the
+		// command and the words before it
+		let words = input.split(' ').collect::<Vec<&str>>();
+		let word = *words.last()?;
+		let lower = word.to_lowercase();
+		let cmd = words[0].to_lowercase();
+
+		// paths can have spaces in them, so they can't be split up like the rest
+		if cmd == ":f" && words.len() > 1 {
+			return Completion::for_path(input);
+		}
+
+		let candidates = match (words.len(), cmd.as_str()) {
+			(1, _) if word.starts_with(':') => Completion::matching(&COMMANDS, &lower),
+			(2, ":t") => Completion::matching(&TAPBACKS, &lower),
+			(2, ":b") => Completion::matching(SETTING_NAMES, &lower),
+			(3, ":b") if words[1] == "theme" || words[1] == "-t" => {
+				Completion::matching(&Colorscheme::names(&settings.custom_colorschemes), &lower)
+			}
+			// chats can be found by their index or their name, but
+			// it's always the index that's put in
+			(2, ":c") | (2, ":pin") | (2, ":unpin") | (2, ":archive") | (2, ":unarchive") => chats
+				.iter()
+				.enumerate()
+				.filter(|(i, c)| {
+					i.to_string().starts_with(word)
+						|| c.display_name.to_lowercase().contains(&lower)
+				})
+				.map(|(i, c)| Candidate {
+					text: i.to_string(),
+					label: format!("{} {}", i, c.display_name),
+					finished: true,
+				})
+				.collect(),
+			_ => Vec::new(),
+		};
+
+		if candidates.is_empty() {
+			return None;
+		}
+
+		Some(Completion {
+			candidates,
+			selected: None,
+			start: input.len() - word.len(),
+			completed: "".to_owned(),
+		})
+	}
+
+	pub fn for_recipients(input: &str, chats: &[Conversation]) -> Option<Completion> {
+		// the recipients are separated by commas, and only the last one is completed
+		let start = input.rfind(',').map(|i| i + 1).unwrap_or(0);
+		let start = start + (input[start..].len() - input[start..].trim_start().len());
+		let word = input[start..].to_lowercase();
+
+		if word.is_empty() {
+			return None;
+		}
+
+		// people can be found by their nicknames, the chats with just them in it, or
+		// the names that we've looked up, and either the name or the address can match.
+		// Nicknames come first since they're the ones that they picked
+		let people = {
+			let state = read_state!();
+			let mut people = state
+				.names
+				.aliases
+				.iter()
+				.map(|(id, name)| (id.to_owned(), name.to_owned()))
+				.collect::<Vec<(String, String)>>();
+
+			people.extend(
+				chats
+					.iter()
+					.filter(|c| {
+						!c.chat_identifier.starts_with("chat") && !c.addresses.contains(',')
+					})
+					.map(|c| (c.chat_identifier.to_owned(), c.display_name.to_owned())),
+			);
+
+			people.extend(state.names.known());
+			people
+		};
+
+		let mut seen = std::collections::HashSet::new();
+
+		let candidates = people
+			.into_iter()
+			.filter(|(id, name)| {
+				!id.starts_with("chat")
+					&& (name.to_lowercase().contains(&word) || id.to_lowercase().starts_with(&word))
+			})
+			.filter(|(id, _)| seen.insert(id.to_owned()))
+			.map(|(id, name)| Candidate {
+				label: if name == id {
+					id.to_owned()
+				} else {
+					format!("{}  {}", name, id)
+				},
+				text: id,
+				finished: true,
+			})
+			.collect::<Vec<Candidate>>();
+
+		if candidates.is_empty() {
+			return None;
+		}
+
+		Some(Completion {
+			candidates,
+			selected: None,
+			start,
+			completed: "".to_owned(),
+		})
+	}
+
+	fn for_path(input: &str) -> Option<Completion> {
+		let (start, typed) = Completion::last_path(input);
+		let expanded = Completion::expand_path(&typed);
+
+		// split it into the directory to look in and the start of the file name
+		let split = expanded
+			.rfind(['/', std::path::MAIN_SEPARATOR])
+			.map(|i| i + 1)
+			.unwrap_or(0);
+		let (dir, prefix) = expanded.split_at(split);
+
+		let entries = std::fs::read_dir(if dir.is_empty() {
+			"."
+		} else {
+			dir
+		})
+		.ok()?;
+
+		let mut names = entries
+			.filter_map(|e| {
+				let entry = e.ok()?;
+				let name = entry.file_name().to_string_lossy().to_string();
+
+				// only show hidden files if they've started typing one
+				if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+				{
+					return None;
+				}
+
+				// `metadata` follows symlinks, so links to directories count as directories
+				let is_dir = std::fs::metadata(entry.path())
+					.map(|m| m.is_dir())
+					.unwrap_or(false);
+
+				Some((name, is_dir))
+			})
+			.collect::<Vec<(String, bool)>>();
+
+		if names.is_empty() {
+			return None;
+		}
+
+		names.sort_by_key(|(name, _)| name.to_lowercase());
+
+		let candidates = names
+			.into_iter()
+			.map(|(name, is_dir)| {
+				let mut path = format!("{}{}", dir, name);
+				if is_dir {
+					path.push('/');
+				}
+
+				Candidate {
+					text: Completion::quote_path(&path, !is_dir),
+					label: if is_dir {
+						format!("{}/", name)
+					} else {
+						name
+					},
+					finished: !is_dir,
+				}
+			})
+			.collect();
+
+		Some(Completion {
+			candidates,
+			selected: None,
+			start,
+			completed: "".to_owned(),
+		})
+	}
+
+	fn last_path(input: &str) -> (usize, String) {
+		// find where the last path in the input starts, and what it is once the quotes
+		// and escapes are taken out. This parses the same way as
+		// `InputView::get_typed_attachments`, so that what's completed is what'll be sent
+		let bad_chars = [' ', '\t', '"', '\\'];
+		let win_bad_chars = [' ', '\t'];
+
+		// skip the command itself
+		let offset = input.find(' ').map(|i| i + 1).unwrap_or(input.len());
+
+		let mut start = offset;
+		let mut path = "".to_owned();
+		let mut in_quotes = false;
+		let mut escaped = false;
+
+		for (i, c) in input[offset..].char_indices() {
+			let i = i + offset;
+
+			if !bad_chars.contains(&c) || escaped || (in_quotes && c != '"') {
+				if cfg!(windows) && escaped && !win_bad_chars.contains(&c) {
+					path.push('\\');
+				}
+
+				path.push(c);
+				escaped = false;
+			} else if c == '\\' {
+				escaped = true;
+			} else if c == '"' {
+				// an opening quote is part of the path we'd replace, but once it's
+				// closed, they've moved on to the next one
+				in_quotes = !in_quotes;
+				start = if in_quotes {
+					i
+				} else {
+					i + 1
+				};
+				path.clear();
+			} else {
+				// unescaped whitespace, so a new path starts after it
+				start = i + c.len_utf8();
+				path.clear();
+			}
+		}
+
+		(start, path)
+	}
+
+	fn expand_path(path: &str) -> String {
+		Completion::expand_path_with(path, dirs::home_dir(), |name| std::env::var(name).ok())
+	}
+
+	fn expand_path_with(
+		path: &str,
+		home: Option<PathBuf>,
+		var: impl Fn(&str) -> Option<String>,
+	) -> String {
+		// expand `~` to `home`, and `$VAR` or `${VAR}` to the
+		// value of the variable, as found by `var`
+		let mut expanded = match (path.strip_prefix('~'), home) {
+			(Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+				let home = home.to_string_lossy().to_string();
+				// `~` on its own should complete to the inside of the home directory
+				format!(
+					"{}{}",
+					home.trim_end_matches('/'),
+					if rest.is_empty() {
+						"/"
+					} else {
+						rest
+					}
+				)
+			}
+			_ => path.to_owned(),
+		};
+
+		// `search` is always moved past what was just looked at, so that values with
+		// a `$` in them aren't expanded again, and a `$` that isn't a variable
+		// doesn't stop the ones after it from being expanded
+		let mut search = 0;
+
+		while let Some(pos) = expanded[search..].find('$') {
+			let dollar = search + pos;
+			let rest = &expanded[dollar + 1..];
+
+			let (name, len) = if let Some(braced) = rest.strip_prefix('{') {
+				match braced.find('}') {
+					Some(end) => (&braced[..end], end + 2),
+					None => {
+						search = dollar + 1;
+						continue;
+					}
+				}
+			} else {
+				let end = rest
+					.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+					.unwrap_or(rest.len());
+				(&rest[..end], end)
+			};
+
+			// leave it as-is if the variable doesn't exist, since it may be a real `$`
+			let value = match var(name) {
+				Some(value) if !name.is_empty() => value,
+				_ => {
+					search = dollar + 1;
+					continue;
+				}
+			};
+
+			expanded.replace_range(dollar..dollar + 1 + len, &value);
+			search = dollar + value.len();
+		}
+
+		expanded
+	}
+
+	fn quote_path(path: &str, close: bool) -> String {
+		// make sure the path will be parsed back as just one file when it's sent.
+		// Quotes are nicest, but can't be used if the path has a quote in it.
+		let bad_chars = [' ', '\t', '"', '\\'];
+
+		// backslashes are path delimiters on windows, so they don't count
+		let needs_quoting = path
+			.chars()
+			.any(|c| bad_chars.contains(&c) && !(cfg!(windows) && c == '\\'));
+
+		if !needs_quoting {
+			path.to_owned()
+		} else if !path.contains('"') {
+			// leave directories open so that they can keep completing inside them
+			format!(
+				"\"{}{}",
+				path,
+				if close {
+					"\""
+				} else {
+					""
+				}
+			)
+		} else {
+			path.chars().fold(String::new(), |mut escaped, c| {
+				if bad_chars.contains(&c) && !(cfg!(windows) && c == '\\') {
+					escaped.push('\\');
+				}
+				escaped.push(c);
+				escaped
+			})
+		}
+	}
+
+	fn matching<T: AsRef<str>>(options: &[T], word: &str) -> Vec<Candidate> {
+		options
+			.iter()
+			.map(|o| o.as_ref())
+			.filter(|o| o.to_lowercase().starts_with(word))
+			.map(|o| Candidate {
+				text: o.to_owned(),
+				label: o.to_owned(),
+				finished: true,
+			})
+			.collect()
+	}
+
+	pub fn draw_popup(
+		&self,
+		frame: &mut Frame<CrosstermBackend<Stdout>>,
+		input_rect: Rect,
+		settings: &Settings,
+	) {
+		// show the candidates in a little box right above the input, or
+		// below it if there's no room (like for the compose address box)
+		let colorscheme = &settings.colorscheme;
+
+		let height = min(self.candidates.len(), POPUP_HEIGHT) as u16 + 2;
+		let below = input_rect.y < height;
+		let bottom = input_rect.y + input_rect.height;
+
+		if self.candidates.len() < 2 || (below && bottom + height > frame.size().height) {
+			return;
+		}
+
+		let label_width = self
+			.candidates
+			.iter()
+			.map(|c| UnicodeWidthStr::width(c.label.as_str()))
+			.max()
+			.unwrap_or(0) as u16;
+		let width = min(max(label_width + 4, 20), input_rect.width);
+		let y = if below {
+			bottom
+		} else {
+			input_rect.y - height
+		};
+		let rect = Rect::new(input_rect.x, y, width, height);
+
+		// scroll so that the selected one is always showing
+		let selected = self.selected.unwrap_or(0);
+		let first = selected.saturating_sub(POPUP_HEIGHT - 1);
+
+		let lines = self
+			.candidates
+			.iter()
+			.enumerate()
+			.skip(first)
+			.take(POPUP_HEIGHT)
+			.map(|(i, c)| {
+				let style = if Some(i) == self.selected {
+					Style::default()
+						.fg(colorscheme.chat_indicator)
+						.add_modifier(Modifier::BOLD)
+				} else {
+					Style::default().fg(colorscheme.text_color)
+				};
+
+				Spans::from(vec![Span::styled(
+					Utilities::truncate(&format!(" {}", c.label), width as usize - 2),
+					style,
+				)])
+			})
+			.collect::<Vec<Spans>>();
+
+		let title = format!("| {}/{} |", selected + 1, self.candidates.len());
+
+		let popup = Paragraph::new(lines).block(
+			Block::default()
+				.title(title)
+				.borders(Borders::ALL)
+				.border_type(BorderType::Rounded)
+				.border_style(Style::default().fg(colorscheme.selected_box)),
+		);
+
+		frame.render_widget(Clear, rect);
+		frame.render_widget(popup, rect);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::input_view::InputView;
+	use std::collections::HashMap;
+
+	#[test]
+	fn last_path_finds_the_last_file() {
+		assert_eq!(Completion::last_path(":f "), (3, "".to_owned()));
+		assert_eq!(
+			Completion::last_path(":f /home/a"),
+			(3, "/home/a".to_owned())
+		);
+		assert_eq!(Completion::last_path(":f /a /b/c"), (6, "/b/c".to_owned()));
+		assert_eq!(Completion::last_path(":f /a  "), (7, "".to_owned()));
+	}
+
+	#[test]
+	fn last_path_handles_quotes() {
+		// an open quote is part of what gets replaced
+		assert_eq!(
+			Completion::last_path(":f \"/my dir/fi"),
+			(3, "/my dir/fi".to_owned())
+		);
+
+		// but once it's closed, the next path starts after it
+		assert_eq!(
+			Completion::last_path(":f \"/a b\" /c"),
+			(10, "/c".to_owned())
+		);
+		assert_eq!(Completion::last_path(":f \"/a b\""), (9, "".to_owned()));
+	}
+
+	#[cfg(not(windows))]
+	#[test]
+	fn last_path_handles_escapes() {
+		assert_eq!(
+			Completion::last_path(":f /my\\ dir/x"),
+			(3, "/my dir/x".to_owned())
+		);
+		assert_eq!(Completion::last_path(":f /a\\\"b"), (3, "/a\"b".to_owned()));
+	}
+
+	#[cfg(not(windows))]
+	#[test]
+	fn quote_path_quotes_when_needed() {
+		assert_eq!(Completion::quote_path("/a/b.txt", true), "/a/b.txt");
+		assert_eq!(Completion::quote_path("/a b.txt", true), "\"/a b.txt\"");
+
+		// directories stay open so that they can keep being completed
+		assert_eq!(Completion::quote_path("/a b/", false), "\"/a b/");
+
+		// paths with quotes in them have to be escaped instead
+		assert_eq!(Completion::quote_path("/a\"b c", true), "/a\\\"b\\ c");
+	}
+
+	#[cfg(not(windows))]
+	#[test]
+	fn quoted_paths_parse_back_the_same() {
+		for path in [
+			"/plain",
+			"/with space/",
+			"/tab\there",
+			"/quote\" here",
+			"/back\\slash",
+		] {
+			// what's sent is the same as what was completed
+			let quoted = Completion::quote_path(path, true);
+			let files = InputView::new().get_typed_attachments(quoted.to_owned());
+			assert_eq!(files, [path]);
+
+			let open = Completion::quote_path(path, false);
+			assert_eq!(Completion::last_path(&format!(":f {}", open)).1, path);
+		}
+	}
+
+	fn expand(path: &str) -> String {
+		let vars = [("DIR", "/some/dir"), ("SELF", "$SELF")]
+			.into_iter()
+			.collect::<HashMap<&str, &str>>();
+
+		Completion::expand_path_with(path, Some(PathBuf::from("/home/me/")), |name| {
+			vars.get(name).map(|v| v.to_string())
+		})
+	}
+
+	#[test]
+	fn expand_path_expands_home() {
+		assert_eq!(expand("~"), "/home/me/");
+		assert_eq!(expand("~/docs"), "/home/me/docs");
+
+		// only `~` on its own, not other people's home directories
+		assert_eq!(expand("~other/docs"), "~other/docs");
+		assert_eq!(expand("/a/~/b"), "/a/~/b");
+
+		// and it's left alone if there's no home directory
+		assert_eq!(
+			Completion::expand_path_with("~/docs", None, |_| None),
+			"~/docs"
+		);
+	}
+
+	#[test]
+	fn expand_path_expands_variables() {
+		assert_eq!(expand("$DIR/a"), "/some/dir/a");
+		assert_eq!(expand("${DIR}a"), "/some/dira");
+
+		// variables that don't exist are left alone, but don't stop the others
+		assert_eq!(expand("$MISSING/$DIR"), "$MISSING//some/dir");
+		assert_eq!(expand("${DIR/$DIR"), "${DIR//some/dir");
+		assert_eq!(expand("a$/b$"), "a$/b$");
+	}
+
+	#[test]
+	fn expand_path_does_not_expand_values_again() {
+		assert_eq!(expand("$SELF/x"), "$SELF/x");
+		assert_eq!(expand("$SELF$DIR"), "$SELF/some/dir");
+	}
+
+	#[test]
+	fn for_path_hides_hidden_files() {
+		let mut dir = std::env::temp_dir();
+		dir.push(format!("smcurser-completion-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+
+		std::fs::create_dir_all(dir.join("sub dir")).unwrap();
+		std::fs::write(dir.join(".hidden"), "").unwrap();
+		std::fs::write(dir.join("visible"), "").unwrap();
+
+		let base = dir.to_string_lossy().to_string();
+		let labels = |input: &str| {
+			Completion::for_path(input)
+				.map(|c| {
+					c.candidates
+						.into_iter()
+						.map(|c| c.label)
+						.collect::<Vec<String>>()
+				})
+				.unwrap_or_default()
+		};
+
+		assert_eq!(labels(&format!(":f {}/", base)), ["sub dir/", "visible"]);
+		assert_eq!(labels(&format!(":f {}/.", base)), [".hidden"]);
+
+		// directories with spaces are quoted, but left open
+		let comp = Completion::for_path(&format!(":f {}/s", base)).unwrap();
+		assert_eq!(comp.candidates[0].text, format!("\"{}/sub dir/", base));
+		assert!(!comp.candidates[0].finished);
+
+		let _ = std::fs::remove_dir_all(&dir);
+	}
+}
@@ -0,0 +1,470 @@
+use crate::{utilities::Utilities, *};
+use image::{
+	imageops::FilterType,
+	io::{Limits, Reader},
+	ImageOutputFormat, Rgba, RgbaImage,
+};
+use sdk::api::APIClient;
+use std::{
+	cmp::{max, min},
+	collections::HashMap,
+	io::Cursor,
+	path::PathBuf,
+};
+use tokio::sync::RwLock;
+use tui::style::Color;
+
+// thumbnails are stored at this size (at most), and then scaled down
+// to fit however many cells they're drawn into
+const THUMBNAIL_SIZE: u32 = 512;
+
+// attachments come from anyone, so these keep a tiny file that decodes to
+// something huge (or just a huge file) from using up all their memory
+const MAX_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+const MAX_IMAGE_SIDE: u32 = 16384;
+const MAX_IMAGE_ALLOC: u64 = 256 * 1024 * 1024;
+
+// we can't easily ask the terminal how big its cells are, so for sixel
+// (where we have to send exact pixels) we assume a pretty common font size
+const CELL_WIDTH: u32 = 8;
+const CELL_HEIGHT: u32 = 16;
+
+// kitty only accepts image data in chunks of this size or smaller
+const KITTY_CHUNK: usize = 4096;
+
+// each row of cells in an image, with the colors for the top
+// and bottom half of each cell
+pub type ImageBlocks = Vec<Vec<(Color, Color)>>;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GraphicsProtocol {
+	Kitty,
+	Iterm,
+	Sixel,
+	Blocks,
+}
+
+impl GraphicsProtocol {
+	pub fn from_setting(setting: &str) -> Option<GraphicsProtocol> {
+		match setting.to_lowercase().as_str() {
+			"auto" => Some(GraphicsProtocol::detect()),
+			"kitty" => Some(GraphicsProtocol::Kitty),
+			"iterm" => Some(GraphicsProtocol::Iterm),
+			"sixel" => Some(GraphicsProtocol::Sixel),
+			"blocks" => Some(GraphicsProtocol::Blocks),
+			_ => None,
+		}
+	}
+
+	fn detect() -> GraphicsProtocol {
+		GraphicsProtocol::detect_from(|name| std::env::var(name).unwrap_or_default())
+	}
+
+	fn detect_from(env: impl Fn(&str) -> String) -> GraphicsProtocol {
+		// there's no reliable way to ask a terminal what it supports without
+		// messing with its input, so we just guess from the environment
+		let var = |name: &str| env(name).to_lowercase();
+
+		let term = var("TERM");
+		let program = var("TERM_PROGRAM");
+
+		// multiplexers don't pass the escape sequences through
+		// to the real terminal, so we can only draw blocks in them
+		if !var("TMUX").is_empty() || term.starts_with("screen") {
+			GraphicsProtocol::Blocks
+		} else if !var("KITTY_WINDOW_ID").is_empty() || term.contains("kitty") {
+			GraphicsProtocol::Kitty
+		} else if program == "iterm.app"
+			|| program == "wezterm"
+			|| !var("ITERM_SESSION_ID").is_empty()
+		{
+			GraphicsProtocol::Iterm
+		} else if term.contains("sixel")
+			|| term.starts_with("foot")
+			|| term.starts_with("mlterm")
+			|| program == "mintty"
+		{
+			GraphicsProtocol::Sixel
+		} else {
+			GraphicsProtocol::Blocks
+		}
+	}
+
+	pub fn leaves_images(&self) -> bool {
+		// iterm and sixel images are drawn into the cells themselves, so they
+		// stay on the screen until something else is drawn over them
+		matches!(self, GraphicsProtocol::Iterm | GraphicsProtocol::Sixel)
+	}
+
+	pub fn encode(&self, img: &RgbaImage, cols: u16, rows: u16) -> String {
+		// get the escape sequence that draws this image at the cursor,
+		// scaled to fit in `cols` x `rows` cells
+		match self {
+			GraphicsProtocol::Kitty => {
+				let data = base64::encode(Graphics::png(img));
+				let chunks = data.as_bytes().chunks(KITTY_CHUNK).collect::<Vec<&[u8]>>();
+				let mut seq = "".to_owned();
+
+				for (i, chunk) in chunks.iter().enumerate() {
+					let more = (i + 1 < chunks.len()) as u8;
+					let chunk = String::from_utf8_lossy(chunk);
+
+					// the first chunk has all the information about the image,
+					// and `q=2` makes sure kitty doesn't respond to it (since
+					// the response would show up as input)
+					if i == 0 {
+						seq.push_str(&format!(
+							"\x1b_Gf=100,a=T,q=2,C=1,c={},r={},m={};{}\x1b\\",
+							cols, rows, more, chunk
+						));
+					} else {
+						seq.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+					}
+				}
+
+				seq
+			}
+			GraphicsProtocol::Iterm => {
+				let data = Graphics::png(img);
+
+				format!(
+					"\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+					data.len(),
+					cols,
+					rows,
+					base64::encode(&data)
+				)
+			}
+			GraphicsProtocol::Sixel => {
+				let scaled = image::imageops::resize(
+					img,
+					cols as u32 * CELL_WIDTH,
+					rows as u32 * CELL_HEIGHT,
+					FilterType::Triangle,
+				);
+
+				Graphics::sixel(&scaled)
+			}
+			// blocks are drawn by tui, so there's nothing to write
+			GraphicsProtocol::Blocks => "".to_owned(),
+		}
+	}
+
+	pub fn clear_images(&self) -> &'static str {
+		// kitty draws images on top of the text, so they need
+		// to be deleted before they can be drawn somewhere else
+		match self {
+			GraphicsProtocol::Kitty => "\x1b_Ga=d,q=2\x1b\\",
+			_ => "",
+		}
+	}
+}
+
+pub struct Graphics;
+
+impl Graphics {
+	pub fn is_image(mime_type: &str) -> bool {
+		mime_type.starts_with("image/")
+	}
+
+	pub async fn load_thumbnail(client: Arc<RwLock<APIClient>>, path: String) -> Option<RgbaImage> {
+		// get the thumbnail from the cache if we already made it,
+		// else download the attachment and make a thumbnail of it
+		let cache = Graphics::thumbnail_file(&path);
+
+		if let Ok(img) = image::open(&cache) {
+			return Some(img.to_rgba8());
+		}
+
+		// the client is only held for the download itself, so that sending
+		// texts doesn't have to wait for the image to be decoded
+		let mut api = client.write().await;
+		let data = api.get_attachment(&path).await.ok()?;
+		drop(api);
+
+		if let Ok(mut state) = STATE.write() {
+			state.attachment_sizes.insert(path.to_owned(), data.len());
+		}
+
+		let thumb = tokio::task::spawn_blocking(move || Graphics::decode_thumbnail(&data))
+			.await
+			.ok()??;
+
+		// it doesn't really matter if this fails; we'll just
+		// have to download it again next time
+		if let Some(dir) = cache.parent() {
+			let _ = std::fs::create_dir_all(dir);
+		}
+		let _ = thumb.save(&cache);
+
+		Some(thumb)
+	}
+
+	fn decode_thumbnail(data: &[u8]) -> Option<RgbaImage> {
+		if data.len() > MAX_IMAGE_BYTES {
+			return None;
+		}
+
+		let mut limits = Limits::default();
+		limits.max_image_width = Some(MAX_IMAGE_SIDE);
+		limits.max_image_height = Some(MAX_IMAGE_SIDE);
+		limits.max_alloc = Some(MAX_IMAGE_ALLOC);
+
+		let mut reader = Reader::new(Cursor::new(data)).with_guessed_format().ok()?;
+		reader.limits(limits);
+
+		let thumb = reader
+			.decode()
+			.ok()?
+			.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+			.to_rgba8();
+
+		Some(thumb)
+	}
+
+	fn thumbnail_file(path: &str) -> PathBuf {
+		// attachment paths are long and full of slashes, so the
+		// thumbnails are named after a (stable) hash of the path
+		let mut file = cache_dir();
+		file.push("thumbnails");
+		file.push(format!("{}.png", Utilities::cache_file_name(path)));
+		file
+	}
+
+	pub fn cell_size(img: &RgbaImage, rows: u16, max_cols: u16) -> (u16, u16) {
+		// find how many cells the image should take up, keeping its aspect ratio.
+		// Cells are about twice as tall as they are wide.
+		let ratio = img.width() as f64 / max(img.height(), 1) as f64;
+		let cols = (ratio * rows as f64 * 2.0).round() as u16;
+
+		if cols <= max_cols {
+			(max(cols, 1), rows)
+		} else {
+			let rows = (max_cols as f64 / ratio / 2.0).round() as u16;
+			(max_cols, max(rows, 1))
+		}
+	}
+
+	pub fn blocks(img: &RgbaImage, rows: u16, max_cols: u16) -> ImageBlocks {
+		// draw the image with half blocks (`▀`), where the foreground is
+		// the top pixel and the background is the bottom pixel. This works
+		// in pretty much every terminal that supports true color.
+		let (cols, rows) = Graphics::cell_size(img, rows, max_cols);
+		let scaled =
+			image::imageops::resize(img, cols as u32, rows as u32 * 2, FilterType::Triangle);
+
+		let color = |p: &Rgba<u8>| {
+			if p[3] < 128 {
+				Color::Reset
+			} else {
+				Color::Rgb(p[0], p[1], p[2])
+			}
+		};
+
+		(0..rows as u32)
+			.map(|y| {
+				(0..cols as u32)
+					.map(|x| {
+						(
+							color(scaled.get_pixel(x, y * 2)),
+							color(scaled.get_pixel(x, y * 2 + 1)),
+						)
+					})
+					.collect()
+			})
+			.collect()
+	}
+
+	fn png(img: &RgbaImage) -> Vec<u8> {
+		let mut data = Cursor::new(Vec::new());
+		let _ = image::DynamicImage::ImageRgba8(img.clone())
+			.write_to(&mut data, ImageOutputFormat::Png);
+		data.into_inner()
+	}
+
+	fn sixel(img: &RgbaImage) -> String {
+		// every pixel is put into a 6x6x6 color cube, so the palette
+		// can be the same for every image
+		let level = |c: u8| (c as usize * 5 + 127) / 255;
+		let index = |p: &Rgba<u8>| {
+			if p[3] < 128 {
+				None
+			} else {
+				Some(level(p[0]) * 36 + level(p[1]) * 6 + level(p[2]))
+			}
+		};
+
+		let (width, height) = img.dimensions();
+
+		// the `1` makes transparent pixels show the background
+		let mut seq = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+
+		for i in 0..216 {
+			seq.push_str(&format!(
+				"#{};2;{};{};{}",
+				i,
+				i / 36 * 20,
+				i / 6 % 6 * 20,
+				i % 6 * 20
+			));
+		}
+
+		// sixels are drawn in bands of 6 pixels tall; for each color in the band,
+		// every column gets a character whose bits say which of its pixels are that color
+		for top in (0..height).step_by(6) {
+			let mut bands: HashMap<usize, Vec<u8>> = HashMap::new();
+
+			for x in 0..width {
+				for y in top..min(top + 6, height) {
+					if let Some(color) = index(img.get_pixel(x, y)) {
+						bands
+							.entry(color)
+							.or_insert_with(|| vec![0; width as usize])[x as usize] |= 1 << (y - top);
+					}
+				}
+			}
+
+			let mut bands = bands.into_iter().collect::<Vec<(usize, Vec<u8>)>>();
+			bands.sort_by_key(|(color, _)| *color);
+
+			for (i, (color, bits)) in bands.iter().enumerate() {
+				// `$` goes back to the start of the band to draw the next color
+				if i > 0 {
+					seq.push('$');
+				}
+
+				seq.push_str(&format!("#{}", color));
+				Graphics::push_sixels(&mut seq, bits);
+			}
+
+			seq.push('-');
+		}
+
+		seq.push_str("\x1b\\");
+		seq
+	}
+
+	fn push_sixels(seq: &mut String, bits: &[u8]) {
+		// write out the sixels, with runs of the same one compressed to `!<count><sixel>`
+		let mut i = 0;
+
+		while i < bits.len() {
+			let run = bits[i..].iter().take_while(|b| **b == bits[i]).count();
+			let sixel = (63 + bits[i]) as char;
+
+			if run > 3 {
+				seq.push_str(&format!("!{}{}", run, sixel));
+			} else {
+				seq.push_str(&sixel.to_string().repeat(run));
+			}
+
+			i += run;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn detect(vars: &[(&str, &str)]) -> GraphicsProtocol {
+		GraphicsProtocol::detect_from(|name| {
+			vars.iter()
+				.find(|(n, _)| *n == name)
+				.map(|(_, v)| v.to_string())
+				.unwrap_or_default()
+		})
+	}
+
+	#[test]
+	fn protocols_are_detected_from_env() {
+		assert!(detect(&[]) == GraphicsProtocol::Blocks);
+		assert!(detect(&[("TERM", "xterm-kitty")]) == GraphicsProtocol::Kitty);
+		assert!(detect(&[("KITTY_WINDOW_ID", "1")]) == GraphicsProtocol::Kitty);
+		assert!(detect(&[("TERM_PROGRAM", "iTerm.app")]) == GraphicsProtocol::Iterm);
+		assert!(detect(&[("TERM_PROGRAM", "WezTerm")]) == GraphicsProtocol::Iterm);
+		assert!(detect(&[("TERM", "foot")]) == GraphicsProtocol::Sixel);
+		assert!(detect(&[("TERM", "xterm-sixel")]) == GraphicsProtocol::Sixel);
+
+		// multiplexers win, even inside a terminal that supports images
+		assert!(
+			detect(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux")]) == GraphicsProtocol::Blocks
+		);
+		assert!(detect(&[("TERM", "screen-256color")]) == GraphicsProtocol::Blocks);
+	}
+
+	#[test]
+	fn cell_size_keeps_the_aspect_ratio() {
+		let img = |w, h| RgbaImage::new(w, h);
+
+		// cells are twice as tall as they are wide
+		assert_eq!(Graphics::cell_size(&img(100, 50), 5, 100), (20, 5));
+		assert_eq!(Graphics::cell_size(&img(50, 50), 5, 100), (10, 5));
+
+		// too wide, so it's shrunk to fit
+		assert_eq!(Graphics::cell_size(&img(100, 10), 5, 20), (20, 1));
+
+		// never less than one cell either way
+		assert_eq!(Graphics::cell_size(&img(1, 100), 5, 100), (1, 5));
+		assert_eq!(Graphics::cell_size(&img(1000, 1), 5, 10), (10, 1));
+		assert_eq!(Graphics::cell_size(&img(0, 0), 5, 10), (1, 5));
+	}
+
+	#[test]
+	fn blocks_use_the_pixel_colors() {
+		let red = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+		let blocks = Graphics::blocks(&red, 2, 100);
+
+		assert_eq!(blocks.len(), 2);
+		assert!(blocks.iter().all(|row| row.len() == 4));
+		assert!(blocks
+			.iter()
+			.flatten()
+			.all(|b| *b == (Color::Rgb(255, 0, 0), Color::Rgb(255, 0, 0))));
+
+		let clear = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 0]));
+		assert!(Graphics::blocks(&clear, 2, 100)
+			.iter()
+			.flatten()
+			.all(|b| *b == (Color::Reset, Color::Reset)));
+	}
+
+	#[test]
+	fn sixels_are_encoded() {
+		let red = RgbaImage::from_pixel(5, 1, Rgba([255, 0, 0, 255]));
+		let seq = Graphics::sixel(&red);
+
+		assert!(seq.starts_with("\x1bP0;1;0q\"1;1;5;1#0;2;0;0;0"));
+		assert!(seq.ends_with("#180!5@-\x1b\\"));
+
+		// the transparent pixel is left out of the first column
+		let mut img = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+		img.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+		assert!(Graphics::sixel(&img).ends_with("#180AB-\x1b\\"));
+
+		// two colors in one band go back to its start with `$`
+		let mut img = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]));
+		img.put_pixel(1, 0, Rgba([0, 0, 255, 255]));
+		assert!(Graphics::sixel(&img).ends_with("#5?@$#180@?-\x1b\\"));
+	}
+
+	#[test]
+	fn sixel_runs_are_compressed() {
+		let mut seq = String::new();
+		Graphics::push_sixels(&mut seq, &[1, 1, 1, 0, 0, 0, 0, 63]);
+		assert_eq!(seq, "@@@!4?~");
+	}
+
+	#[test]
+	fn huge_images_are_not_decoded() {
+		let small = Graphics::png(&RgbaImage::new(10, 10));
+		assert!(Graphics::decode_thumbnail(&small).is_some());
+
+		// compresses to almost nothing, but is too wide to decode
+		let wide = Graphics::png(&RgbaImage::new(MAX_IMAGE_SIDE + 1, 1));
+		assert!(Graphics::decode_thumbnail(&wide).is_none());
+
+		assert!(Graphics::decode_thumbnail(b"not an image").is_none());
+		assert!(Graphics::decode_thumbnail(&vec![0; MAX_IMAGE_BYTES + 1]).is_none());
+	}
+}
@@ -1,9 +1,9 @@
-use crate::*;
-use crossterm::event::KeyCode;
+use crate::{completion::Completion, utilities::Utilities, *};
+use crossterm::event::{KeyCode, KeyModifiers};
 use std::{
 	cmp::{max, min},
-	fs::read_dir,
 	io::Stdout,
+	path::PathBuf,
 	vec::Vec,
 };
 use tui::{
@@ -16,14 +16,50 @@ use tui::{
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+// how many killed pieces of text are remembered to be yanked back
+const KILL_RING_SIZE: usize = 16;
+
+// how many changes can be undone
+const UNDO_SIZE: usize = 100;
+
+// what they're searching for in their history with ctrl+r
+pub struct HistorySearch {
+	pub query: String,
+	pub index: Option<usize>, // the index in `last_commands` of the current match
+	pub failed: bool,         // if nothing matches the query
+	pub original: String,     // what was typed before they started searching
+}
+
+// typing or deleting a bunch of characters in a row is undone all at
+// once, instead of one character at a time
+#[derive(Clone, Copy, PartialEq)]
+pub enum EditKind {
+	Typing,
+	Deleting,
+	Other,
+}
+
 pub struct InputView {
-	pub input: String,                // the text that is input to this view
-	pub bounds: (u16, u16),           // the substring of the input that is shown
-	pub right_offset: u16,            // the cursor's offset from the right side of the input
+	pub input: String,                            // the text that is input to this view
+	pub bounds: (u16, u16),                       // the substring of the input that is shown
+	pub right_offset: u16, // the cursor's offset from the right side of the input
 	pub last_width: u16, // last width that the view recorded. Since input views are always one line, height changes don't affect them.
 	pub last_commands: Vec<String>, // last commands that were input, so that you can tab up through them
 	pub tabbed_up: Option<u16>,     // how far tabbed up through the most recent commands you are
 	pub custom_title: Option<String>, // custom title to display with this
+	pub line_scroll: u16,           // how many lines are scrolled past, when multiline
+	pub text_width: u16,            // the width that the text was last wrapped to, when multiline
+	pub kill_ring: Vec<String>,     // text that was killed with ctrl+w/u/k, so it can be yanked back
+	pub yanked: Option<(usize, usize)>, // the kill ring index and end of the last yank, for alt+y
+	pub vi_normal: bool,            // if it's in vi's normal mode, when vi mode is on
+	pub vi_pending: Option<char>,   // the operator (d, c, or y) that's waiting for a motion
+	pub vi_insert: Option<(String, usize)>, // the command that started insert mode, and where, for `.`
+	pub vi_last_change: Option<(String, String)>, // the last change's keys and inserted text, for `.`
+	pub undo_stack: Vec<(String, u16)>, // the input and right_offset before each change
+	pub redo_stack: Vec<(String, u16)>, // the changes that were undone, so they can be redone
+	pub last_edit: Option<EditKind>, // what the last change was, so similar changes can be grouped
+	pub search: Option<HistorySearch>, // the search through history that's going on, if any
+	pub completion: Option<Completion>, // the candidates that tab is cycling through, if any
 }
 
 impl InputView {
@@ -36,6 +72,45 @@ impl InputView {
 			last_commands: Vec::new(),
 			tabbed_up: None,
 			custom_title: None,
+			line_scroll: 0,
+			text_width: 0,
+			kill_ring: Vec::new(),
+			yanked: None,
+			vi_normal: false,
+			vi_pending: None,
+			vi_insert: None,
+			vi_last_change: None,
+			undo_stack: Vec::new(),
+			redo_stack: Vec::new(),
+			last_edit: None,
+			search: None,
+			completion: None,
+		}
+	}
+
+	fn title(&self, settings: &Settings) -> String {
+		// the title with the mode shown next to it, if they're using vi mode
+		let title = self.custom_title.as_ref().unwrap_or(&settings.input_title);
+
+		if let Some(ref search) = self.search {
+			// like bash's `(reverse-i-search)`
+			return format!(
+				"| search history: {}{} |",
+				search.query,
+				if search.failed {
+					" (no match)"
+				} else {
+					""
+				}
+			);
+		}
+
+		if !settings.vi_mode {
+			title.to_owned()
+		} else if self.vi_normal {
+			format!("{} -- NORMAL -- ", title)
+		} else {
+			format!("{} -- INSERT -- ", title)
 		}
 	}
 
@@ -49,7 +124,12 @@ impl InputView {
 	) {
 		let colorscheme = &settings.colorscheme;
 
-		let title = self.custom_title.as_ref().unwrap_or(&settings.input_title);
+		let title = self.title(settings);
+
+		if settings.multiline_input {
+			self.draw_multiline(frame, rect, selected, take_cursor, settings);
+			return;
+		}
 
 		// if it's not the same width, the terminal has been resized. Reset some
 		// stuff so that everything doesn't spazz out when you try to draw it.
@@ -99,6 +179,154 @@ impl InputView {
 		}
 	}
 
+	fn draw_multiline(
+		&mut self,
+		frame: &mut Frame<CrosstermBackend<Stdout>>,
+		rect: Rect,
+		selected: bool,
+		take_cursor: bool,
+		settings: &Settings,
+	) {
+		// instead of scrolling sideways, the text is wrapped
+		// onto as many lines as it needs
+		let colorscheme = &settings.colorscheme;
+		let title = self.title(settings);
+
+		// `scroll` still uses this, so it has to be set here too
+		self.last_width = frame.size().width;
+		self.text_width = rect.width.saturating_sub(2);
+		let (lines, positions) = self.wrapped_lines(self.text_width as usize);
+		let (row, col) = positions[positions.len() - 1 - self.right_offset as usize];
+
+		// scroll so that the line with the cursor is showing
+		let visible = max(rect.height.saturating_sub(2), 1);
+		if (row as u16) < self.line_scroll {
+			self.line_scroll = row as u16;
+		} else if row as u16 >= self.line_scroll + visible {
+			self.line_scroll = row as u16 + 1 - visible;
+		}
+
+		let input_spans = lines
+			.into_iter()
+			.map(|l| Spans::from(vec![Span::raw(l)]))
+			.collect::<Vec<Spans>>();
+
+		let input_widget = Paragraph::new(input_spans)
+			.block(
+				Block::default()
+					.title(title.as_str())
+					.borders(Borders::ALL)
+					.border_type(BorderType::Rounded)
+					.border_style(Style::default().fg(if selected {
+						colorscheme.selected_box
+					} else {
+						colorscheme.unselected_box
+					})),
+			)
+			.scroll((self.line_scroll, 0));
+		frame.render_widget(input_widget, rect);
+
+		if take_cursor {
+			frame.set_cursor(
+				rect.x + 1 + col as u16,
+				rect.y + 1 + row as u16 - self.line_scroll,
+			);
+		}
+	}
+
+	pub fn height(&self, width: u16, settings: &Settings) -> u16 {
+		// how tall the view should be (with its borders) to fit
+		// all the lines of its input, up to the max height
+		if !settings.multiline_input {
+			return 3;
+		}
+
+		let (lines, _) = self.wrapped_lines(width.saturating_sub(2) as usize);
+		min(lines.len() as u16, max(settings.input_max_height, 1)) + 2
+	}
+
+	fn wrapped_lines(&self, width: usize) -> (Vec<String>, Vec<(usize, usize)>) {
+		// split the input into the lines that will be shown, breaking at newlines
+		// and wrapping when they're too wide. Also get the line and column that
+		// each grapheme is at (plus the end of the input), so that we know
+		// where to put the cursor.
+		let graphemes = self.input.graphemes(true).collect::<Vec<&str>>();
+
+		// leave room at the end of each line for the cursor to sit
+		let width = max(width, 2) - 1;
+
+		let mut lines = vec!["".to_owned()];
+		let mut positions = Vec::with_capacity(graphemes.len() + 1);
+		let mut line_width = 0;
+
+		for g in graphemes.into_iter() {
+			if g == "\n" || g == "\r\n" {
+				positions.push((lines.len() - 1, line_width));
+				lines.push("".to_owned());
+				line_width = 0;
+				continue;
+			}
+
+			let g_width = UnicodeWidthStr::width(g);
+
+			if line_width + g_width > width && line_width > 0 {
+				lines.push("".to_owned());
+				line_width = 0;
+			}
+
+			positions.push((lines.len() - 1, line_width));
+
+			if let Some(line) = lines.last_mut() {
+				line.push_str(g);
+			}
+			line_width += g_width;
+		}
+
+		positions.push((lines.len() - 1, line_width));
+
+		(lines, positions)
+	}
+
+	pub fn is_multiline(&self, settings: &Settings) -> bool {
+		// if the input is taking up more than one line right now
+		settings.multiline_input && self.wrapped_lines(self.text_width as usize).0.len() > 1
+	}
+
+	pub fn move_vertically(&mut self, up: bool) {
+		// move the cursor to the line above or below it, staying in about
+		// the same column, like every other text editor
+		let (_, positions) = self.wrapped_lines(self.text_width as usize);
+		let cursor = positions.len() - 1 - self.right_offset as usize;
+		let (row, col) = positions[cursor];
+
+		let target_row = if up {
+			match row.checked_sub(1) {
+				Some(r) => r,
+				None => return,
+			}
+		} else {
+			row + 1
+		};
+
+		// find the farthest position on that line that isn't past the cursor's column
+		let target = positions
+			.iter()
+			.enumerate()
+			.filter(|(_, (r, _))| *r == target_row)
+			.take_while(|(_, (_, c))| *c <= col)
+			.last()
+			.or_else(|| {
+				positions
+					.iter()
+					.enumerate()
+					.find(|(_, (r, _))| *r == target_row)
+			});
+
+		if let Some((idx, _)) = target {
+			self.right_offset = (positions.len() - 1 - idx) as u16;
+		}
+	}
+
 	pub fn route_keycode(&mut self, code: KeyCode) {
 		// just decide to which function the specified keycode should go
 		match code {
@@ -109,10 +337,508 @@ impl InputView {
 		}
 	}
 
+	pub fn route_editing_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+		// the readline shortcuts that every shell has. Returns whether or
+		// not the key was one of them, so that the app can handle it if it wasn't
+		let ctrl = modifiers == KeyModifiers::CONTROL;
+		let alt = modifiers == KeyModifiers::ALT;
+		let cursor = self.cursor();
+		let len = self.input.graphemes(true).count();
+
+		match code {
+			KeyCode::Home => self.move_cursor_to(0),
+			KeyCode::End => self.move_cursor_to(len),
+			KeyCode::Delete => self.handle_delete(),
+			KeyCode::Char('a') if ctrl => self.move_cursor_to(0),
+			KeyCode::Char('e') if ctrl => self.move_cursor_to(len),
+			KeyCode::Char('b') if alt => self.move_cursor_to(self.word_start(false)),
+			KeyCode::Char('f') if alt => self.move_cursor_to(self.word_end()),
+			KeyCode::Char('w') if ctrl => self.kill(self.word_start(true), cursor),
+			KeyCode::Char('d') if alt => self.kill(cursor, self.word_end()),
+			KeyCode::Char('u') if ctrl => self.kill(0, cursor),
+			KeyCode::Char('k') if ctrl => self.kill(cursor, len),
+			KeyCode::Char('y') if ctrl => self.yank(),
+			KeyCode::Char('y') if alt => self.yank_pop(),
+			_ => return false,
+		}
+
+		true
+	}
+
+	pub fn route_vi_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+		// handle a key the way vi would. Returns whether or not it was handled,
+		// so that the app can handle it normally if it wasn't
+		if !self.vi_normal {
+			// escape only switches to normal mode if there's something to edit
+			// (or if they were just changing something); else it does what it always does
+			if code == KeyCode::Esc && (!self.input.is_empty() || self.vi_insert.is_some()) {
+				self.leave_insert();
+				return true;
+			}
+			return false;
+		}
+
+		let c = match code {
+			KeyCode::Char(c) if !modifiers.contains(KeyModifiers::CONTROL) => c,
+			KeyCode::Esc if self.vi_pending.is_some() => {
+				self.vi_pending = None;
+				return true;
+			}
+			_ => return false,
+		};
+
+		self.vi_command(c);
+
+		// there's nothing to do in normal mode with nothing typed,
+		// so let them start typing again
+		if self.input.is_empty() && self.vi_normal {
+			self.vi_normal = false;
+			self.vi_pending = None;
+		}
+
+		true
+	}
+
+	fn vi_command(&mut self, c: char) {
+		let len = self.input.graphemes(true).count();
+		let cursor = self.cursor();
+
+		// if they've already typed an operator, this is its motion
+		if let Some(op) = self.vi_pending.take() {
+			let range = if c == op {
+				// `dd`, `cc` and `yy` work on the whole input
+				Some((0, len))
+			} else {
+				// `cw` acts like `ce`, since that's what people actually want
+				let motion = if op == 'c' && c == 'w' {
+					'e'
+				} else {
+					c
+				};
+
+				self.vi_motion(motion).map(|target| {
+					// `e` and `$` include the character they land on
+					let end_inclusive = (motion == 'e' || motion == '$') as usize;
+
+					if target >= cursor {
+						(cursor, min(target + end_inclusive, len))
+					} else {
+						(target, cursor)
+					}
+				})
+			};
+
+			if let Some((start, end)) = range {
+				self.vi_operate(op, start, end, &format!("{}{}", op, c));
+			}
+
+			return;
+		}
+
+		if let Some(target) = self.vi_motion(c) {
+			self.move_cursor_to(target);
+			return;
+		}
+
+		match c {
+			'd' | 'c' | 'y' => self.vi_pending = Some(c),
+			'x' => self.vi_operate('d', cursor, min(cursor + 1, len), "x"),
+			'D' => self.vi_operate('d', cursor, len, "D"),
+			'C' => self.vi_operate('c', cursor, len, "C"),
+			's' => self.vi_operate('c', cursor, min(cursor + 1, len), "s"),
+			'i' => self.enter_insert(cursor, "i"),
+			'a' => self.enter_insert(min(cursor + 1, len), "a"),
+			'I' => self.enter_insert(0, "I"),
+			'A' => self.enter_insert(len, "A"),
+			'p' | 'P' => {
+				if let Some(text) = self.kill_ring.last().cloned() {
+					self.save_undo();
+
+					if c == 'p' {
+						self.move_cursor_to(min(cursor + 1, len));
+					}
+					self.insert_str(&text);
+
+					// vi leaves the cursor on the last pasted character
+					let end = self.cursor();
+					self.move_cursor_to(end.saturating_sub(1));
+					self.vi_last_change = Some((c.to_string(), "".to_owned()));
+				}
+			}
+			'u' => {
+				self.undo();
+				self.clamp_normal_cursor();
+			}
+			'.' => self.vi_repeat(),
+			_ => (),
+		}
+	}
+
+	fn vi_motion(&self, motion: char) -> Option<usize> {
+		// find where the cursor would go with this motion
+		let graph = self.input.graphemes(true).collect::<Vec<&str>>();
+		let len = graph.len();
+		let mut idx = self.cursor();
+
+		// vi has 3 kinds of characters: whitespace, word characters (letters,
+		// numbers, and underscores), and punctuation. Words are runs of the same kind.
+		let kind = |i: usize| {
+			let g = graph[i];
+			if g.trim().is_empty() {
+				0
+			} else if g.chars().any(|c| c.is_alphanumeric() || c == '_') {
+				1
+			} else {
+				2
+			}
+		};
+
+		match motion {
+			'h' => Some(idx.saturating_sub(1)),
+			'l' => Some(min(idx + 1, len.saturating_sub(1))),
+			'0' => Some(0),
+			'$' => Some(len.saturating_sub(1)),
+			'w' => {
+				if idx < len && kind(idx) != 0 {
+					let start = kind(idx);
+					while idx < len && kind(idx) == start {
+						idx += 1;
+					}
+				}
+
+				while idx < len && kind(idx) == 0 {
+					idx += 1;
+				}
+
+				Some(idx)
+			}
+			'b' => {
+				while idx > 0 && kind(idx - 1) == 0 {
+					idx -= 1;
+				}
+
+				if idx > 0 {
+					let start = kind(idx - 1);
+					while idx > 0 && kind(idx - 1) == start {
+						idx -= 1;
+					}
+				}
+
+				Some(idx)
+			}
+			'e' => {
+				idx += 1;
+				while idx < len && kind(idx) == 0 {
+					idx += 1;
+				}
+
+				if idx < len {
+					let start = kind(idx);
+					while idx + 1 < len && kind(idx + 1) == start {
+						idx += 1;
+					}
+				}
+
+				Some(min(idx, len.saturating_sub(1)))
+			}
+			_ => None,
+		}
+	}
+
+	fn vi_operate(&mut self, op: char, start: usize, end: usize, keys: &str) {
+		// run an operator over the graphemes from `start` to `end`
+		if op == 'y' {
+			let graph = self.input.graphemes(true).collect::<Vec<&str>>();
+			self.kill_ring.push(graph[start..end].join(""));
+			if self.kill_ring.len() > KILL_RING_SIZE {
+				self.kill_ring.remove(0);
+			}
+
+			self.move_cursor_to(start);
+			return;
+		}
+
+		self.save_undo();
+		self.kill(start, end);
+
+		if op == 'c' {
+			self.vi_normal = false;
+			self.vi_insert = Some((keys.to_owned(), start));
+		} else {
+			self.vi_last_change = Some((keys.to_owned(), "".to_owned()));
+			self.clamp_normal_cursor();
+		}
+	}
+
+	fn enter_insert(&mut self, index: usize, keys: &str) {
+		self.save_undo();
+		self.move_cursor_to(index);
+		self.vi_normal = false;
+		self.vi_insert = Some((keys.to_owned(), index));
+	}
+
+	fn leave_insert(&mut self) {
+		// remember what was typed, so that `.` can type it again
+		if let Some((keys, start)) = self.vi_insert.take() {
+			let graph = self.input.graphemes(true).collect::<Vec<&str>>();
+			let cursor = self.cursor();
+			let inserted = graph.get(start..cursor).unwrap_or_default().join("");
+
+			self.vi_last_change = Some((keys, inserted));
+		}
+
+		self.vi_normal = true;
+
+		// vi moves the cursor back onto the last character that was typed
+		let cursor = self.cursor();
+		self.move_cursor_to(cursor.saturating_sub(1));
+	}
+
+	fn clamp_normal_cursor(&mut self) {
+		// in normal mode, the cursor is on a character, so it can't be past the end
+		let len = self.input.graphemes(true).count();
+		if len > 0 && self.cursor() >= len {
+			self.move_cursor_to(len - 1);
+		}
+	}
+
+	fn vi_repeat(&mut self) {
+		// run the last change again, and type the same thing if it went into insert mode
+		let (keys, inserted) = match self.vi_last_change.clone() {
+			Some(change) => change,
+			None => return,
+		};
+
+		for c in keys.chars() {
+			self.vi_command(c);
+		}
+
+		if !self.vi_normal {
+			self.insert_str(&inserted);
+			self.leave_insert();
+		}
+	}
+
+	fn save_undo(&mut self) {
+		self.save_undo_for(EditKind::Other);
+	}
+
+	fn save_undo_for(&mut self, kind: EditKind) {
+		// save what's here now so that it can be undone back to, unless
+		// this is just more of the same kind of change as the last one
+		self.redo_stack.clear();
+
+		let grouped = kind != EditKind::Other && self.last_edit == Some(kind);
+		self.last_edit = Some(kind);
+
+		let state = (self.input.to_owned(), self.right_offset);
+
+		if !grouped && self.undo_stack.last() != Some(&state) {
+			self.undo_stack.push(state);
+
+			if self.undo_stack.len() > UNDO_SIZE {
+				self.undo_stack.remove(0);
+			}
+		}
+	}
+
+	pub fn undo(&mut self) {
+		if let Some(state) = self.undo_stack.pop() {
+			let current = (self.input.to_owned(), self.right_offset);
+			self.redo_stack.push(current);
+			self.restore(state);
+		}
+	}
+
+	pub fn redo(&mut self) {
+		if let Some(state) = self.redo_stack.pop() {
+			let current = (self.input.to_owned(), self.right_offset);
+			self.undo_stack.push(current);
+			self.restore(state);
+		}
+	}
+
+	fn restore(&mut self, (input, right_offset): (String, u16)) {
+		self.input = input;
+		self.right_offset = right_offset;
+		self.last_edit = None;
+		self.scroll(false, 0);
+	}
+
+	fn cursor(&self) -> usize {
+		// the index of the grapheme that the cursor is in front of
+		self.input.graphemes(true).count() - self.right_offset as usize
+	}
+
+	fn move_cursor_to(&mut self, index: usize) {
+		// scrolling handles making sure the cursor is still showing.
+		// Typing somewhere else should be undone separately, too
+		let cursor = self.cursor();
+		self.last_edit = None;
+
+		if index > cursor {
+			self.scroll(true, (index - cursor) as u16);
+		} else {
+			self.scroll(false, (cursor - index) as u16);
+		}
+	}
+
+	fn is_word(grapheme: &str, whitespace_only: bool) -> bool {
+		// ctrl+w deletes everything up to whitespace (like in bash), but
+		// alt+b/f/d stop at anything that isn't a letter or number
+		if whitespace_only {
+			!grapheme.trim().is_empty()
+		} else {
+			grapheme.chars().any(|c| c.is_alphanumeric())
+		}
+	}
+
+	fn word_start(&self, whitespace_only: bool) -> usize {
+		// find the start of the word that the cursor is in or after
+		let graph = self.input.graphemes(true).collect::<Vec<&str>>();
+		let mut idx = self.cursor();
+
+		while idx > 0 && !InputView::is_word(graph[idx - 1], whitespace_only) {
+			idx -= 1;
+		}
+
+		while idx > 0 && InputView::is_word(graph[idx - 1], whitespace_only) {
+			idx -= 1;
+		}
+
+		idx
+	}
+
+	fn word_end(&self) -> usize {
+		// find the end of the word that the cursor is in or before
+		let graph = self.input.graphemes(true).collect::<Vec<&str>>();
+		let mut idx = self.cursor();
+
+		while idx < graph.len() && !InputView::is_word(graph[idx], false) {
+			idx += 1;
+		}
+
+		while idx < graph.len() && InputView::is_word(graph[idx], false) {
+			idx += 1;
+		}
+
+		idx
+	}
+
+	fn kill(&mut self, start: usize, end: usize) {
+		// remove the graphemes from `start` to `end`, and save them so that they can be yanked
+		if start >= end {
+			return;
+		}
+
+		self.save_undo();
+
+		let mut graph = self.input.graphemes(true).collect::<Vec<&str>>();
+		let killed = graph.drain(start..end).collect::<Vec<&str>>().join("");
+		let len = graph.len();
+		self.input = graph.join("");
+
+		self.kill_ring.push(killed);
+		if self.kill_ring.len() > KILL_RING_SIZE {
+			self.kill_ring.remove(0);
+		}
+
+		// the cursor ends up where the killed text started
+		self.right_offset = (len - start) as u16;
+		self.scroll(false, 0);
+	}
+
+	fn insert_str(&mut self, text: &str) {
+		// insert a whole string at the cursor, and put the cursor after it
+		self.save_undo();
+
+		let mut graph = self.input.graphemes(true).collect::<Vec<&str>>();
+		let cursor = graph.len() - self.right_offset as usize;
+
+		graph.insert(cursor, text);
+		self.input = graph.join("");
+
+		self.scroll(true, 0);
+	}
+
+	fn yank(&mut self) {
+		// put the most recently killed text back in at the cursor
+		let index = match self.kill_ring.len().checked_sub(1) {
+			Some(idx) => idx,
+			None => return,
+		};
+
+		let text = self.kill_ring[index].to_owned();
+		self.insert_str(&text);
+		self.yanked = Some((index, self.cursor()));
+	}
+
+	fn yank_pop(&mut self) {
+		// if they just yanked something, replace it with the
+		// text that was killed before it, like in emacs
+		let (index, end) = match self.yanked {
+			Some(yanked) => yanked,
+			None => return,
+		};
+
+		self.save_undo();
+
+		let graph = self.input.graphemes(true).collect::<Vec<&str>>();
+		let yanked = match self.kill_ring.get(index) {
+			Some(text) => text,
+			None => return,
+		};
+		let yanked_len = yanked.graphemes(true).count();
+
+		// make sure that nothing's been typed since then
+		if end != self.cursor()
+			|| end < yanked_len
+			|| graph[end - yanked_len..end].join("") != *yanked
+		{
+			self.yanked = None;
+			return;
+		}
+
+		let next = (index + self.kill_ring.len() - 1) % self.kill_ring.len();
+		let text = self.kill_ring[next].to_owned();
+
+		let mut graph = graph
+			.into_iter()
+			.map(|g| g.to_owned())
+			.collect::<Vec<String>>();
+		graph.splice(end - yanked_len..end, vec![text.to_owned()]);
+		self.input = graph.join("");
+
+		// the cursor should be after the new text
+		let new_end = end - yanked_len + text.graphemes(true).count();
+		self.right_offset = (self.input.graphemes(true).count() - new_end) as u16;
+		self.scroll(false, 0);
+
+		self.yanked = Some((next, new_end));
+	}
+
+	pub fn handle_delete(&mut self) {
+		// like backspace, but for the grapheme after the cursor
+		self.save_undo_for(EditKind::Deleting);
+
+		let mut graph = self.input.graphemes(true).collect::<Vec<&str>>();
+		let cursor = graph.len() - self.right_offset as usize;
+
+		if cursor < graph.len() {
+			graph.remove(cursor);
+			self.input = graph.join("");
+			self.right_offset -= 1;
+		}
+
+		self.scroll(false, 0);
+	}
+
 	pub fn append_char(&mut self, ch: char) {
 		// input it at the specified place
 		// also have to work with unicode here so that we don't
 		// insert in the middle of a utf char
+		self.save_undo_for(EditKind::Typing);
+
 		let mut graph = self.input.graphemes(true).collect::<Vec<&str>>();
 		let len = graph.len();
 
@@ -124,9 +850,24 @@ impl InputView {
 		self.scroll(true, 0);
 	}
 
+	pub fn set_input(&mut self, input: String) {
+		// replace everything that's input with this, and put the cursor at the end
+		self.input = input;
+		self.right_offset = 0;
+		self.scroll(false, 0);
+	}
+
 	pub fn handle_escape(&mut self) {
+		// this is undoable, so that accidentally hitting escape doesn't lose a long message
+		if !self.input.is_empty() {
+			self.save_undo();
+		}
+
 		self.input = "".to_owned();
 		self.right_offset = 0;
+		self.vi_normal = false;
+		self.vi_pending = None;
+		self.tabbed_up = None;
 
 		// once again, makes sure that the input will display nicely when redrawn
 		self.scroll(false, 0);
@@ -135,6 +876,8 @@ impl InputView {
 	pub fn handle_backspace(&mut self) {
 		// have to handle this all as unicode so that people can backspace
 		// a whole unicode character
+		self.save_undo_for(EditKind::Deleting);
+
 		let mut graph = self.input.graphemes(true).collect::<Vec<&str>>();
 		let len = graph.len();
 
@@ -149,17 +892,95 @@ impl InputView {
 		self.scroll(false, 0);
 	}
 
-	pub fn handle_tab(&mut self) {
-		// if the  first 3 characters are `:f ` or `:F `, then they're pressing tab to get file
-		// path completion. Handle that separately.
+	pub fn cycle_completion(&mut self, forward: bool) {
+		// put the next (or previous) candidate in place of the word that's being completed
+		self.save_undo();
 
-		let graphemes = self.input.graphemes(true).collect::<Vec<&str>>();
+		let completion = match self.completion {
+			Some(ref mut completion) => completion,
+			None => return,
+		};
 
-		if graphemes.len() > 2 && &graphemes[..3].join("").to_lowercase() == ":f " {
-			self.handle_tab_completion();
-		} else {
-			self.input.push('\t');
+		let len = completion.candidates.len();
+		let next = match completion.selected {
+			Some(sel) if forward => (sel + 1) % len,
+			Some(sel) => (sel + len - 1) % len,
+			None if forward => 0,
+			None => len - 1,
+		};
+		completion.selected = Some(next);
+
+		let candidate = &completion.candidates[next];
+		let mut text = candidate.text.to_owned();
+
+		// if it's the only option, they're done with this word
+		// (unless it's a directory, since they'll want to go into it)
+		if len == 1 && candidate.finished {
+			text.push(' ');
+		}
+
+		self.input.truncate(completion.start);
+		self.input.push_str(&text);
+		completion.completed = self.input.to_owned();
+
+		if len == 1 {
+			self.completion = None;
 		}
+
+		self.right_offset = 0;
+		self.scroll(false, 0);
+	}
+
+	pub fn complete_prefix(&mut self) -> bool {
+		// if all the candidates start the same way, fill that in first, like bash.
+		// Returns whether or not there was anything to fill in
+		let completion = match self.completion {
+			Some(ref mut completion) if completion.candidates.len() > 1 => completion,
+			_ => return false,
+		};
+
+		let first = completion.candidates[0].text.as_str();
+		let prefix_len = completion.candidates[1..]
+			.iter()
+			.map(|c| {
+				first
+					.chars()
+					.zip(c.text.chars())
+					.take_while(|(a, b)| a == b)
+					.map(|(a, _)| a.len_utf8())
+					.sum::<usize>()
+			})
+			.min()
+			.unwrap_or(0);
+
+		// the candidates might not start with what they typed (e.g. if `~` was
+		// expanded), so only fill it in if it would actually add something
+		let prefix = first[..prefix_len].to_owned();
+		if self.input[completion.start..].starts_with(&prefix) {
+			return false;
+		}
+
+		self.save_undo();
+
+		let completion = match self.completion {
+			Some(ref mut completion) => completion,
+			None => return false,
+		};
+
+		self.input.truncate(completion.start);
+		self.input.push_str(&prefix);
+		completion.completed = self.input.to_owned();
+
+		self.right_offset = 0;
+		self.scroll(false, 0);
+
+		true
+	}
+
+	pub fn handle_tab(&mut self) {
+		// completion (for commands, file paths, etc) is handled by the app, since
+		// it needs to know about the chats and settings. So this is just a tab.
+		self.append_char('\t');
 	}
 
 	pub fn get_typed_attachments(&self, input: String) -> Vec<String> {
@@ -227,119 +1048,6 @@ impl InputView {
 		files
 	}
 
-	pub fn handle_tab_completion(&mut self) {
-		// So this is my messy attempt at tab completion. It actually works ok-ish
-		// I think it works on Windows but I can't say for certain
-
-		let mut splits = self.input.split(' ').collect::<Vec<&str>>();
-		splits.remove(0);
-		let input = splits.join(" ");
-
-		// this gets a list of the currently input attachments,
-		// with support for escaping spaces with backslashes and quotes
-		let incomplete_opt = self.get_typed_attachments(input);
-
-		// if there are no attachments input, just exit
-		if incomplete_opt.is_empty() {
-			return;
-		}
-
-		let dir_char = if cfg!(windows) {
-			"\\"
-		} else {
-			"/"
-		};
-
-		// get the path for the attachment that hasn't fully been input yet
-		let incomplete = incomplete_opt
-			.last()
-			.expect("Couldn't get last character of incomplete_opt");
-
-		// separate it by "/", join all but last since that is probably
-		// the file that hasn't fully been input yet
-		let mut top_dirs = incomplete.split(dir_char).collect::<Vec<&str>>();
-		let first_file = top_dirs
-			.drain(top_dirs.len() - 1..top_dirs.len())
-			.collect::<Vec<&str>>()
-			.join("");
-
-		// Here we iterate over the parent directories and make sure they weren't
-		// escaping a "/" with a "\" in the file that wasn't fully input yet
-		let mut to_drop = 0;
-
-		for c in top_dirs.iter().rev() {
-			if !c.is_empty() && c.chars().last().unwrap_or('-') == '\\' {
-				to_drop += 1;
-			} else {
-				break;
-			}
-		}
-
-		// Set file to the whole untyped file name, including the possibly escaped sections
-		let file = if to_drop > 0 {
-			let poss_files = top_dirs
-				.drain(top_dirs.len() - to_drop..top_dirs.len())
-				.collect::<Vec<&str>>()
-				.join("");
-
-			format!("{}{}{}", poss_files, dir_char, first_file)
-		} else {
-			first_file
-		};
-
-		// dir = the whole parent directory for the file they were entering
-		let dir = top_dirs.join(dir_char);
-		let dir_contents = read_dir(&dir);
-
-		if let Ok(items) = dir_contents {
-			for item in items.flatten() {
-				let path = item.path();
-
-				// tmp_path = the file or dir name (including dot
-				// between name and extension or trailing slash for directory
-				let tmp_path = format!(
-					"{}{}{}",
-					if let Some(fs) = path.file_stem() {
-						fs.to_str().unwrap_or("")
-					} else {
-						""
-					},
-					if let Some(ex) = path.extension() {
-						format!(".{}", ex.to_str().unwrap_or(""))
-					} else {
-						"".to_owned()
-					},
-					if path.is_dir() {
-						dir_char
-					} else {
-						""
-					}
-				);
-
-				let path_str = tmp_path.as_str();
-
-				// if the file that is currently being iterated over is
-				// the same length or shorter than what they've input,
-				// don't even try to match it
-				if path_str.len() <= file.len() {
-					continue;
-				}
-
-				// If it's a possibility for the file they were trying to input,
-				// auto-fill the input string with the whole file path
-				if path_str[..file.len()] == file {
-					let full_path = format!("{}{}{}", dir, dir_char, path_str);
-
-					self.input.truncate(self.input.len() - incomplete.len());
-					self.input = format!("{}{}", self.input, full_path);
-
-					self.last_width = 0;
-					break;
-				}
-			}
-		}
-	}
-
 	pub fn scroll(&mut self, right: bool, distance: u16) {
 		// this is the actual scrolling part
 
@@ -367,12 +1075,12 @@ impl InputView {
 				.as_str(),
 		) as u16;
 
-		let greater_than_view = display_len_to_offset > self.last_width - 2;
+		let greater_than_view = display_len_to_offset > self.last_width.saturating_sub(2);
 		let bound_before_end = (self.bounds.1 as i32) < display_len_to_offset as i32;
 
 		let cursor_at_beginning = display_len_to_offset <= self.bounds.0;
 
-		let less_than_view = self.last_width - 2 > display_len;
+		let less_than_view = self.last_width.saturating_sub(2) > display_len;
 
 		if greater_than_view && bound_before_end {
 			// set it so that the cursor will be at the farthest right end
@@ -382,7 +1090,7 @@ impl InputView {
 		} else if cursor_at_beginning {
 			// sets the cursor to the leftmost end of the drawn input view
 			self.bounds.0 = len - self.right_offset;
-			self.bounds.1 = min(self.bounds.0 + self.last_width - 3, len);
+			self.bounds.1 = min(self.bounds.0 + self.last_width.saturating_sub(3), len);
 		} else if less_than_view {
 			// just sets the bounds to the full string, basically, since its length
 			// is less than the length of the view that it will be drawn in.
@@ -406,7 +1114,7 @@ impl InputView {
 					if self.last_commands.len() as u16 > tu + 1 {
 						// if we tabbed up and we can still do so more, do so.
 						self.tabbed_up = Some(tu + 1);
-						self.input = self.last_commands[tu as usize].as_str().to_owned();
+						self.input = self.last_commands[tu as usize + 1].as_str().to_owned();
 					}
 				}
 			}
@@ -426,6 +1134,201 @@ impl InputView {
 			}
 		}
 
+		// the commands can be shorter than where the cursor was
+		self.right_offset = 0;
 		self.scroll(false, 0);
 	}
+
+	pub fn add_to_history(&mut self, command: &str, max_size: usize) {
+		// put the command at the top of the history, taking out any older copies
+		// of it so that they don't have to go through the same one over and over
+		if command.trim().is_empty() {
+			return;
+		}
+
+		self.last_commands.retain(|c| c != command);
+		self.last_commands.insert(0, command.to_owned());
+		self.last_commands.truncate(max_size);
+
+		InputView::save_history(&self.last_commands);
+	}
+
+	pub fn load_history(max_size: usize) -> Vec<String> {
+		let mut commands: Vec<String> = Utilities::read_toml(&InputView::history_file())
+			.and_then(|table| {
+				table.get("commands")?.as_array().map(|cmds| {
+					cmds.iter()
+						.filter_map(|c| c.as_str().map(|c| c.to_owned()))
+						.collect()
+				})
+			})
+			.unwrap_or_default();
+
+		// in case they've made `history_size` smaller since it was saved
+		commands.truncate(max_size);
+		commands
+	}
+
+	fn can_save(command: &str) -> bool {
+		// only the commands that can't have a text or a password in them are
+		// saved. Anything else (including typos, since `:S hi` could have been
+		// meant as a text) is left out.
+		const SAVED: [&str; 21] = [
+			":q",
+			":c",
+			":h",
+			":r",
+			":a",
+			":o",
+			":f",
+			":paste",
+			":t",
+			":n",
+			":dt",
+			":dc",
+			":pin",
+			":unpin",
+			":alias",
+			":members",
+			":mute",
+			":unmute",
+			":archive",
+			":unarchive",
+			":y",
+		];
+
+		let cmd = command.split(' ').next().unwrap_or_default().to_lowercase();
+		SAVED.contains(&cmd.as_str())
+	}
+
+	fn save_history(commands: &[String]) {
+		// the rest are still remembered for this session, but they never get
+		// written to the disk, since they could have anything in them
+		let commands = commands
+			.iter()
+			.filter(|c| InputView::can_save(c))
+			.cloned()
+			.collect::<Vec<String>>();
+
+		let mut table = toml::value::Table::new();
+		table.insert("commands".to_owned(), commands.into());
+
+		Utilities::write_toml(&InputView::history_file(), table);
+	}
+
+	fn history_file() -> PathBuf {
+		let mut file = config_dir();
+		file.push("history.toml");
+		file
+	}
+
+	pub fn start_search(&mut self) {
+		// start searching back through the history, or go to the
+		// next match if they're already searching
+		match self.search {
+			Some(ref search) => {
+				let from = search.index.map(|i| i + 1).unwrap_or(0);
+				self.find_match(from);
+			}
+			None => {
+				self.search = Some(HistorySearch {
+					query: "".to_owned(),
+					index: None,
+					failed: false,
+					original: self.input.to_owned(),
+				});
+			}
+		}
+	}
+
+	pub fn route_search_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+		// handle a key while they're searching. Returns whether or not it was handled
+		let search = match self.search {
+			Some(ref mut search) => search,
+			None => return false,
+		};
+
+		match code {
+			KeyCode::Char('r') if modifiers == KeyModifiers::CONTROL => self.start_search(),
+			// ctrl+g cancels the search in bash, so it does here too
+			KeyCode::Char('g') if modifiers == KeyModifiers::CONTROL => self.cancel_search(),
+			KeyCode::Esc => self.cancel_search(),
+			KeyCode::Char(c) if !modifiers.contains(KeyModifiers::CONTROL) => {
+				search.query.push(c);
+				let from = search.index.unwrap_or(0);
+				self.find_match(from);
+			}
+			KeyCode::Backspace => {
+				search.query.pop();
+				self.find_match(0);
+			}
+			// anything else keeps the match and stops searching. Enter
+			// doesn't run the command, so they can look at it first
+			KeyCode::Enter => self.search = None,
+			_ => {
+				self.search = None;
+				return false;
+			}
+		}
+
+		true
+	}
+
+	fn find_match(&mut self, from: usize) {
+		// find the most recent command (starting at `from`) that has the query in it
+		let search = match self.search {
+			Some(ref mut search) => search,
+			None => return,
+		};
+
+		let found = self
+			.last_commands
+			.iter()
+			.enumerate()
+			.skip(from)
+			.find(|(_, c)| c.contains(&search.query));
+
+		search.failed = found.is_none();
+
+		if let Some((idx, command)) = found {
+			search.index = Some(idx);
+			self.input = command.to_owned();
+
+			// put the cursor at the start of the match, like bash does
+			let before = command.find(&search.query).unwrap_or(0);
+			self.right_offset = command[before..].graphemes(true).count() as u16;
+			self.scroll(false, 0);
+		}
+	}
+
+	fn cancel_search(&mut self) {
+		// put back what they were typing before they started searching
+		if let Some(search) = self.search.take() {
+			self.set_input(search.original);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn only_safe_commands_are_saved() {
+		assert!(InputView::can_save(":c 2"));
+		assert!(InputView::can_save(":F ~/a.png"));
+		assert!(InputView::can_save(":q"));
+
+		// texts and settings (which could be the password) never are
+		assert!(!InputView::can_save(":s hey"));
+		assert!(!InputView::can_save(":es hey"));
+		assert!(!InputView::can_save(":e"));
+		assert!(!InputView::can_save(":b p hunter2"));
+		assert!(!InputView::can_save(":B password hunter2"));
+
+		// and neither are typos or anything else
+		assert!(!InputView::can_save(":sx hey"));
+		assert!(!InputView::can_save("hello"));
+		assert!(!InputView::can_save(""));
+	}
 }
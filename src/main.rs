@@ -1,9 +1,16 @@
 mod app;
+mod attachments_view;
+mod chat_picker;
 mod chats_view;
 mod colorscheme;
+mod completion;
+mod graphics;
 mod input_view;
 mod messages_view;
 mod models;
+mod names;
+mod paste;
+mod previews;
 mod settings;
 mod state;
 mod utilities;
@@ -74,7 +81,7 @@ async fn main() -> anyhow::Result<()> {
 	}
 }
 
-const HELP_MSG: [&str; 33] = [
+const HELP_MSG: [&str; 67] = [
 	"COMMANDS:",
 	":h, :H -",
 	"displays this help message",
@@ -84,39 +91,81 @@ const HELP_MSG: [&str; 33] = [
 	"scrolls up in the selected window",
 	"h, l -",
 	"switches selected window between messages and conversations",
+	"/ -",
+	"filters the conversations to the ones whose name, addresses, or latest text contain what you type after it. The list narrows as you type, and Enter keeps the filter. The numbers beside each conversation stay the same, so ':c' still opens the one you see. While the conversations box is selected, u only shows unread conversations and g only shows group chats (press them again to show everything). The filters are shown in the title of the box, and Esc (with nothing typed) clears them",
 	":q, :Q, Ctrl+c -",
 	"exits SMCurser, cleaning up",
 	":c, :C -",
-	"this should be immediately followed by a number, specifically the index of the conversation whose texts you want to view. the indices are displayed to the left of each conversation in the leftmost box. eg ':c 25'",
+	"this should be followed by either the index of the conversation whose texts you want to view (the indices are displayed to the left of each conversation in the leftmost box, e.g. ':c 25'), or part of its name, identifier, or addresses (e.g. ':c jon'). The letters don't have to be right next to each other, so ':c jsmth' finds 'John Smith'. If more than one chat matches, or you just type ':c', a picker shows up where you can keep typing to narrow it down, move with Up/Down or Ctrl+p/Ctrl+n, open the selected chat with Enter, or cancel with Esc",
 	":s, :S -",
 	"sends a text; must be followed by at least one character. Follow the ':s' with a space, and then the body of your text. e.g. ':s hey friend!'",
 	":t, :T -",
 	"sends a tapback for the currently selected chat. Enter :t <value>, where <value> is either 'love', 'like', 'dislike', 'laugh', 'emphasize', or 'question'.",
 	":f, :F -",
 	"sends attachments to the specified chat. Specify the files specifically as full path strings, surrounded by single or double quotes, e.g. \"/home/user/Documents/file.txt\" or '/home/user/Pictures/file.jpeg'. You can select multiple files, and they will all be send in the order that they were specified. Also supports tab completion",
+	"Alt+Enter, Shift+Enter - ",
+	"when 'multiline_input' is on, adds a new line to what you're typing, instead of sending it. The input box grows to fit what you've typed, up to 'input_max_height' lines, and Up and Down move between the lines instead of going through your past commands. Shift+Enter only works in terminals that tell SMCurser about it",
+	"Ctrl+a, Ctrl+e, Home, End, Alt+b, Alt+f - ",
+	"moves the cursor to the start or end of what you've typed, or back or forward a word. Ctrl+w deletes the word before the cursor, Alt+d deletes the word after it, Ctrl+u deletes everything before the cursor and Ctrl+k everything after it. Whatever is deleted with those can be put back with Ctrl+y, and Alt+y right after Ctrl+y swaps it for what was deleted before that",
+	"Tab, Shift+Tab - ",
+	"completes what you're typing: command names, tapbacks after ':t', settings after ':b' (and colorschemes after ':b theme'), chats by their index or name after ':c', and file paths after ':f'. If there's more than one option, they're shown above the input, and pressing Tab again goes through them. Enter keeps the one that's picked",
+	"Up, Down - ",
+	"when you're not writing multiple lines, these go back and forth through the commands you've run. They're saved between sessions, up to 'history_size' of them, without any repeats. Texts that you've sent are only remembered until you quit, so that they're never saved on your disk",
+	"Ctrl+r - ",
+	"searches back through the commands you've run, like in bash. Type to search, press Ctrl+r again for an older match, Enter to keep the match in the input, or Esc to cancel",
+	"Esc (while writing a text) - ",
+	"clears the input, but saves what you were writing as a draft for the current chat. Drafts are also saved when you switch chats or quit, and are put back in the input when you go back to that chat. Chats with a draft have a '✎' beside them",
+	"Ctrl+z, Ctrl+shift+z - ",
+	"undoes or redoes the last change to what you're typing, including clearing it with Esc. A bunch of characters typed or deleted in a row are undone together. These keys can be changed with the 'undo_key' and 'redo_key' settings, since some terminals can't tell Ctrl+shift+z apart from Ctrl+z",
+	"Esc (with vi mode on) - ",
+	"switches to normal mode, like in vi. There, h, l, w, b, e, 0, and $ move the cursor, d, c, and y delete, change, or copy up to wherever those move to (or the whole input, with dd, cc, and yy), x deletes the character under the cursor, p pastes, u undoes, and . repeats the last change. i, a, I, and A go back to insert mode, and the current mode is shown in the title of the input box",
+	":e, :es, Ctrl+x - ",
+	"this opens your editor ($VISUAL or $EDITOR) so that you can write a long text more easily. Anything after ':e' is put in the editor to start with, and once you close the editor, what you wrote is put into the input box as a ':s' command for you to send. ':es' sends it as soon as you close the editor instead. Ctrl+x does the same as ':e' with whatever you've already typed. It's Ctrl+x and not Ctrl+e since Ctrl+e moves to the end of the line, like in readline, but it can be changed with the 'editor_key' setting. SMCurser doesn't redraw or show hints while the editor is open, but texts that come in then are shown once you close it",
+	":paste, Ctrl+v - ",
+	"sends the image or files that are in your clipboard to the current chat, after showing what type and how big they are and asking you to confirm with 'y'. On Linux, images are pasted with wl-paste or xclip, and on macOS with pngpaste, so one of those needs to be installed",
 	":a, :A -",
-	"this, along with the number of the attachment, will open the selected attachment in your browser. For example, if you see 'Attachment 5: image/jpeg', type ':a 5' and the attachment will be opened to be viewed in your browser",
+	"this, along with the number of the attachment, will download the selected attachment to your download directory (or the one set with 'download_dir'). For example, if you see 'Attachment 5: image/jpeg', type ':a 5' and the attachment will be downloaded. Files that are already there are never overwritten; the new one is named like 'image (1).jpeg' instead. If 'open_downloads' is on, it will be opened once it's downloaded. If you don't give a number, this shows all the attachments in the conversation instead, with their type, size (once downloaded), date, and sender. In there, j/k move, space marks an attachment, d downloads the marked attachments (or the selected one), o or enter opens the selected attachment, y copies its path, f filters by type, and q goes back",
 	":b, :B -",
 	"these allow you to change variables in settings at runtime. All the available variables to change can be found by passing in the '-h' flag when running SMCurser. To change one, you would simply need to do ':b <var> <val>'. E.G. ':b host 192.168.0.127'. there is no need to encapsulate strings in quotes, and booleans can be typed as either true/false or True/False. If you change something that is displayed on the screen, such as window titles, the windows will not be automatically reloaded.",
 	":r, :R -",
 	"this reloads the chats, getting current chats from the currently set ip address and port.",
 	":n, :N - ",
-	"this shows a new composition box, from which you can send a text to a new conversation (or to a conversation that you can\'t quickly access). Type in the recipient(s), then hit enter, and you\'ll be able to enter the body of the message. Once you enter the body, you won\'t be able to change the recipients. Hit ctrl+g to send the text.",
+	"this shows a new composition box, from which you can send a text to a new conversation (or to a conversation that you can\'t quickly access). Type in the recipient(s), separated by commas, then hit enter, and you\'ll be able to enter the body of the message. Tab completes the recipient you're typing from your conversations, the names SMCurser has looked up, and the nicknames you've given people, and each recipient has to be a phone number, an email, a nickname, or the identifier of a chat you already have before you can move on to the body. Once you enter the body, you won\'t be able to change the recipients. Hit ctrl+g to send the text.",
 	":dc - ",
-	"this deletes the current conversation (if you follow it with the chat_id, e.g. `:dc +11231231234`). If you don't, it will prompt you to do so.",
+	"this deletes the current conversation, or the conversation whose chat_id you specify (e.g. `:dc +11231231234`). You will be asked to confirm with 'y' before it is deleted, unless you have turned confirmations off with ':b confirm false'.",
 	":dt - ",
-	"this deletes the currently selected text. There is no prompting, it immediately deletes it, so make sure that you are careful with this comand",
+	"this deletes the currently selected text. You will be asked to confirm with 'y' before it is deleted, unless you have turned confirmations off.",
+	":pin, :unpin - ",
+	"pins or unpins the current conversation, or the one whose index you give (e.g. ':pin 3'). Pinned conversations are shown in their own section at the top of the conversations box, and stay there when new texts come in for the others. The server can't pin conversations, so they're saved in SMCurser's config directory instead",
+	":alias - ",
+	"gives the current conversation a nickname, e.g. ':alias Mom', which is shown instead of its name. ':alias' on its own gets rid of it. You can also add them yourself in contacts.toml in SMCurser's config directory, like '\"+11231231234\" = \"Mom\"'. SMCurser never changes that file; the ones set with ':alias' are saved in aliases.toml next to it instead, and win over the ones in contacts.toml. They're used for conversation names, the names above texts in group chats, notifications, and ':members', and you can type them in the address box when composing a new text",
+	":members - ",
+	"shows everyone in the current conversation, with their names and addresses. In group chats, each person's name and the underline of their texts are given their own color, which is also shown here. Press any key to close it",
+	":mute, :unmute - ",
+	"stops notifications for the current conversation, and stops it from being marked as unread when texts come in. Give it how long to mute it for, like ':mute 30m', ':mute 2h', or ':mute 1d', or leave that out to mute it until you ':unmute' it",
+	":archive, :unarchive - ",
+	"hides the current conversation (or the one whose index you give) from the conversations box, until a new text comes in for it. While the conversations box is selected, a switches between showing the archived conversations and the rest of them",
 	":y, :Y - ",
 	"this copies the text from the currently selected text onto into your clipboard",
+	":o, :O - ",
+	"this, along with the number of a link, will open that link in your browser (or with the program set as 'link_opener'). Links in messages are followed by their number, e.g. 'https://example.com [3]' can be opened with ':o 3'",
 ];
 
-const CMD_HELP: [&str; 52] = [
+const CMD_HELP: [&str; 86] = [
 	"usage: \x1b[1m./smcurser [flags] [options]\x1b[0m",
 	"",
 	"\x1b[1mFlags:\x1b[0m",
 	"    \x1b[1m--help\x1b[0m                      Show this help menu",
 	"    \x1b[1m--secure\x1b[0m                    Connect to REST Host with TLS",
 	"    \x1b[1m--notifications\x1b[0m             Show notifications when receiving new messages",
+	"    \x1b[1m--confirm\x1b[0m, \x1b[1m-v\x1b[0m               Ask for confirmation before running destructive commands (on by default)",
+	"    \x1b[1m--hyperlinks\x1b[0m, \x1b[1m-H\x1b[0m            Make links in messages clickable in terminals that support it (on by default)",
+	"    \x1b[1m--link-previews\x1b[0m, \x1b[1m-P\x1b[0m         Fetch and show previews of links in messages (off by default)",
+	"    \x1b[1m--open-downloads\x1b[0m, \x1b[1m-X\x1b[0m        Open attachments once they're downloaded (off by default)",
+	"    \x1b[1m--multiline-input\x1b[0m, \x1b[1m-M\x1b[0m       Wrap the input onto multiple lines and let Alt+Enter add new lines (off by default)",
+	"    \x1b[1m--vi-mode\x1b[0m, \x1b[1m-V\x1b[0m               Edit the input boxes like vi, with normal and insert modes (off by default)",
+	"    \x1b[1m--chat-previews\x1b[0m, \x1b[1m-C\x1b[0m         Show the latest text and its time under each chat (off by default)",
+	"    \x1b[1m--unread-counts\x1b[0m, \x1b[1m-K\x1b[0m         Show how many texts have come in for each chat since you last opened it (on by default)",
 	"",
 	"\x1b[1mOptions:\x1b[0m",
 	"    \x1b[1m--config\x1b[0m, \x1b[1m-c\x1b[0m <value>            The config file to use",
@@ -141,6 +190,8 @@ const CMD_HELP: [&str; 52] = [
 	"                   Default: \x1b[32;1m>\x1b[0m",
 	"    \x1b[1m--unread-indicator\x1b[0m, \x1b[1m-z\x1b[0m <value>  The character to use to indicate all chats with unread messages",
 	"                   Default: \x1b[32;1m•\x1b[0m",
+	"    \x1b[1m--draft-indicator\x1b[0m, \x1b[1m-W\x1b[0m <value>   The character to use to indicate chats with a saved draft",
+	"                   Default: \x1b[32;1m✎\x1b[0m",
 	"    \x1b[1m--chat-underline\x1b[0m, \x1b[1m-d\x1b[0m <value>    The character to repeat to create the underline of the text messages",
 	"                   Default: \x1b[32;1m▔\x1b[0m",
 	"    \x1b[1m--chat-title\x1b[0m, \x1b[1m-a\x1b[0m <value>        The string to use as the title of the chats box",
@@ -157,6 +208,30 @@ const CMD_HELP: [&str; 52] = [
 	"                   Default: \x1b[32;1m| message: |\x1b[0m",
 	"    \x1b[1m--theme\x1b[0m, \x1b[1m-t\x1b[0m <value>             The colorscheme to use",
 	"                   Default: \x1b[32;1mforest\x1b[0m",
+	"    \x1b[1m--link-opener\x1b[0m, \x1b[1m-O\x1b[0m <value>       The program to open links and files with",
+	"                   Default: \x1b[32;1mxdg-open, open on macOS, rundll32 url.dll on Windows\x1b[0m",
+	"    \x1b[1m--undo-key\x1b[0m, \x1b[1m-U\x1b[0m <value>          The key that undoes changes to the input, e.g. ctrl+z",
+	"                   Default: \x1b[32;1mctrl+z\x1b[0m",
+	"    \x1b[1m--redo-key\x1b[0m, \x1b[1m-Y\x1b[0m <value>          The key that redoes changes to the input that were undone",
+	"                   Default: \x1b[32;1mctrl+shift+z\x1b[0m",
+	"    \x1b[1m--editor-key\x1b[0m, \x1b[1m-E\x1b[0m <value>        The key that opens what you're typing in your editor",
+	"                   Default: \x1b[32;1mctrl+x\x1b[0m",
+	"    \x1b[1m--preview-allow\x1b[0m, \x1b[1m-A\x1b[0m <value>     Comma-separated domains to fetch link previews from. If empty, all domains are allowed",
+	"                   Default: \x1b[32;1mNone\x1b[0m",
+	"    \x1b[1m--preview-deny\x1b[0m, \x1b[1m-D\x1b[0m <value>      Comma-separated domains to never fetch link previews from",
+	"                   Default: \x1b[32;1mNone\x1b[0m",
+	"    \x1b[1m--inline-images\x1b[0m, \x1b[1m-I\x1b[0m <value>     How to show thumbnails of image attachments; one of off, auto, kitty, iterm, sixel, or blocks",
+	"                   Default: \x1b[32;1moff\x1b[0m",
+	"    \x1b[1m--image-height\x1b[0m, \x1b[1m-R\x1b[0m <value>      The most lines that a thumbnail can take up",
+	"                   Default: \x1b[32;1m10\x1b[0m",
+	"    \x1b[1m--input-max-height\x1b[0m, \x1b[1m-T\x1b[0m <value>  The most lines of text that the input box can grow to",
+	"                   Default: \x1b[32;1m5\x1b[0m",
+	"    \x1b[1m--history-size\x1b[0m, \x1b[1m-N\x1b[0m <value>      How many commands to remember, between sessions too (0 for none)",
+	"                   Default: \x1b[32;1m1000\x1b[0m",
+	"    \x1b[1m--download-dir\x1b[0m, \x1b[1m-L\x1b[0m <value>      The directory to download attachments to",
+	"                   Default: \x1b[32;1mYour download directory\x1b[0m",
+	"    \x1b[1m--download-subdir\x1b[0m, \x1b[1m-S\x1b[0m <value>   The subdirectory of the download directory for each chat's attachments; {chat} is replaced with the chat's id and {name} with its name",
+	"                   Default: \x1b[32;1mNone\x1b[0m",
 	"    \x1b[1m--poll-input\x1b[0m, \x1b[1m-l\x1b[0m <value>        The amount of milliseconds to poll for input",
 	"                   Default: \x1b[32;1m10\x1b[0m",
 	"    \x1b[1m--timeout\x1b[0m, \x1b[1m-g\x1b[0m <value>           The timeout for API queries in seconds",
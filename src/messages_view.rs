@@ -1,18 +1,26 @@
-use crate::{models::*, utilities::*, *};
+use crate::{
+	attachments_view::AttachmentsView, graphics::*, models::*, names::NameCache,
+	previews::LinkPreview, utilities::*, *,
+};
 use clipboard::{ClipboardContext, ClipboardProvider};
+use crossterm::{cursor, queue, style as cstyle};
+use image::RgbaImage;
 use sdk::{api::*, models::*};
 use std::{
 	cmp::{max, min},
-	io::Stdout,
+	collections::HashMap,
+	io::{Stdout, Write},
+	path::PathBuf,
 };
 use tokio::sync::RwLock;
 use tui::{
 	layout::Rect,
-	style::{Modifier, Style},
+	style::{Color, Modifier, Style},
 	terminal::Frame,
 	text::{Span, Spans},
 	widgets::{Block, BorderType, Borders, Paragraph},
 };
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 pub struct MessagesView {
@@ -20,6 +28,16 @@ pub struct MessagesView {
 	pub messages: Vec<Message>,
 	pub line_list: Vec<MessageLine>,
 	pub attachments: Vec<String>,
+	pub links: Vec<String>,
+	pub visible_links: Vec<LinkPlacement>,
+	// None if the preview is still loading or couldn't be loaded
+	pub previews: HashMap<String, Option<LinkPreview>>,
+	// same as above, but for the thumbnails of image attachments
+	pub thumbnails: HashMap<String, Option<RgbaImage>>,
+	pub image_blocks: HashMap<String, ImageBlocks>,
+	pub graphics: Option<GraphicsProtocol>,
+	pub visible_images: Vec<ImagePlacement>,
+	pub drawn_images: Vec<ImagePlacement>,
 	pub last_width: u16,
 	pub last_height: u16,
 	pub y_bounds: (u16, u16), // .0 is top, .1 is bottom
@@ -35,6 +53,14 @@ impl MessagesView {
 			messages: Vec::new(),
 			line_list: Vec::new(),
 			attachments: Vec::new(),
+			links: Vec::new(),
+			visible_links: Vec::new(),
+			previews: LinkPreview::load_cache(),
+			thumbnails: HashMap::new(),
+			image_blocks: HashMap::new(),
+			graphics: None,
+			visible_images: Vec::new(),
+			drawn_images: Vec::new(),
 			last_width: 0,
 			last_height: 0,
 			y_bounds: (0, 0),
@@ -63,18 +89,50 @@ impl MessagesView {
 			self.last_height = rect.height;
 		}
 
+		let link_style = Style::default()
+			.fg(colorscheme.link)
+			.add_modifier(Modifier::UNDERLINED);
+
+		// find where the top of the view is
+		let scrolled = !self.messages.is_empty() && self.line_list.len() as u16 >= rect.height;
+		let top = if scrolled {
+			self.y_bounds.0 as usize
+		} else {
+			0
+		};
+
+		// this has to be done before we make the spans, since the images that
+		// are drawn with a graphics protocol are left blank for tui
+		self.place_images(rect, top);
+
+		// only the images that are showing are downloaded, so that opening a chat
+		// with a bunch of pictures doesn't download all of them at once
+		self.request_thumbnails(top, rect.height.saturating_sub(2) as usize);
+
+		// so that the colors match the names that are shown
+		let aliases = read_state!().names.aliases.clone();
+
 		// create the vector of spans that will be drawn to the terminal
 		let item_list: Vec<Spans> = self
 			.line_list
 			.iter()
 			.map(|l| {
+				// in group chats, everyone's name and texts get their own color
+				let sender_color = self
+					.messages
+					.get(l.relative_index)
+					.filter(|m| !m.is_from_me)
+					.and_then(|m| m.sender.as_ref())
+					.and_then(|s| colorscheme.sender_color(aliases.get(s).unwrap_or(s)));
+
 				let style = match l.message_type {
 					// set the style for the specific line based on its type
 					MessageLineType::Blank
 					| MessageLineType::TimeDisplay
-					| MessageLineType::Text => Style::default().fg(colorscheme.text_color),
+					| MessageLineType::Text
+					| MessageLineType::Image(..) => Style::default().fg(colorscheme.text_color),
 					MessageLineType::Sender => Style::default()
-						.fg(colorscheme.text_color)
+						.fg(sender_color.unwrap_or(colorscheme.text_color))
 						.add_modifier(Modifier::ITALIC | Modifier::BOLD),
 					MessageLineType::Underline => {
 						Style::default().fg(if l.relative_index as u16 == self.selected_msg {
@@ -82,15 +140,52 @@ impl MessagesView {
 						} else if l.from_me {
 							colorscheme.my_underline
 						} else {
-							colorscheme.their_underline
+							sender_color.unwrap_or(colorscheme.their_underline)
 						})
 					}
-					MessageLineType::Typing => Style::default()
+					MessageLineType::Typing | MessageLineType::Preview => Style::default()
 						.fg(colorscheme.text_color)
 						.add_modifier(Modifier::ITALIC),
 				};
 
-				Spans::from(vec![Span::styled(l.text.as_str(), style)])
+				// links get their own style, so split them out of the line
+				let mut spans = Vec::new();
+				let mut last = 0;
+
+				match l.message_type {
+					MessageLineType::Text => {
+						for (start, end) in Utilities::find_urls(&l.text) {
+							spans.push(Span::styled(&l.text[last..start], style));
+							spans.push(Span::styled(&l.text[start..end], link_style));
+							last = end;
+						}
+					}
+					MessageLineType::Image(att, row) => {
+						// images that aren't drawn with a graphics protocol
+						// are drawn here with half blocks instead
+						let drawn = self.visible_images.iter().any(|p| p.attachment == att);
+						let blocks = self
+							.attachments
+							.get(att)
+							.and_then(|a| self.image_blocks.get(a))
+							.and_then(|b| b.get(row as usize));
+
+						if let (false, Some(blocks)) = (drawn, blocks) {
+							last = l.text.len() - blocks.len();
+							spans.push(Span::styled(&l.text[..last], style));
+
+							for (top, bottom) in blocks.iter() {
+								spans
+									.push(Span::styled("▀", Style::default().fg(*top).bg(*bottom)));
+							}
+						}
+					}
+					_ => (),
+				}
+
+				spans.push(Span::styled(&l.text[last..], style));
+
+				Spans::from(spans)
 			})
 			.collect();
 
@@ -109,10 +204,198 @@ impl MessagesView {
 		let mut messages_widget = Paragraph::new(item_list).block(messages_border);
 
 		// scroll to the correct location
-		if !self.messages.is_empty() && self.line_list.len() as u16 >= rect.height {
+		if scrolled {
 			messages_widget = messages_widget.scroll((self.y_bounds.0, 0));
 		}
 		frame.render_widget(messages_widget, rect);
+
+		// and remember where each link that is currently showing was drawn
+		self.place_links(rect, top);
+	}
+
+	fn place_images(&mut self, rect: Rect, top: usize) {
+		// find where each image that is completely on screen will be drawn.
+		// Images that are only partly showing are drawn with blocks instead,
+		// since graphics protocols can't draw only part of an image.
+		let inner_height = rect.height.saturating_sub(2) as usize;
+
+		self.visible_images = match self.graphics {
+			Some(GraphicsProtocol::Blocks) | None => Vec::new(),
+			_ => self
+				.line_list
+				.iter()
+				.enumerate()
+				.skip(top)
+				.take(inner_height)
+				.filter_map(|(i, l)| match l.message_type {
+					MessageLineType::Image(att, 0) => {
+						let blocks = self.image_blocks.get(self.attachments.get(att)?)?;
+						let cols = blocks.first()?.len();
+
+						if i + blocks.len() > top + inner_height {
+							return None;
+						}
+
+						Some(ImagePlacement {
+							x: rect.x + 1 + (l.text.len() - cols) as u16,
+							y: rect.y + 1 + (i - top) as u16,
+							cols: cols as u16,
+							rows: blocks.len() as u16,
+							attachment: att,
+						})
+					}
+					_ => None,
+				})
+				.collect(),
+		};
+	}
+
+	pub fn has_stale_images(&self, show: bool) -> bool {
+		// check if there are images on the screen that tui doesn't know
+		// about, which won't be drawn in the same place this time
+		let leaves_images = self.graphics.map(|g| g.leaves_images()).unwrap_or(false);
+		let wanted: &[ImagePlacement] = if show {
+			&self.visible_images
+		} else {
+			&[]
+		};
+
+		leaves_images && !self.drawn_images.is_empty() && self.drawn_images != wanted
+	}
+
+	pub fn write_images<W: Write>(&mut self, out: &mut W, show: bool) -> std::io::Result<()> {
+		// like with hyperlinks, tui can't draw images, so we draw them
+		// after it's done. We only redraw them if they've moved, though,
+		// since they're pretty big to send.
+		let protocol = match self.graphics {
+			Some(GraphicsProtocol::Blocks) | None => return Ok(()),
+			Some(protocol) => protocol,
+		};
+
+		let wanted = if show {
+			self.visible_images.clone()
+		} else {
+			Vec::new()
+		};
+
+		if wanted == self.drawn_images {
+			return Ok(());
+		}
+
+		queue!(
+			out,
+			cursor::SavePosition,
+			cstyle::Print(protocol.clear_images())
+		)?;
+
+		for img in wanted.iter() {
+			let thumb = self
+				.attachments
+				.get(img.attachment)
+				.and_then(|a| self.thumbnails.get(a));
+
+			if let Some(Some(thumb)) = thumb {
+				queue!(
+					out,
+					cursor::MoveTo(img.x, img.y),
+					cstyle::Print(protocol.encode(thumb, img.cols, img.rows))
+				)?;
+			}
+		}
+
+		queue!(out, cursor::RestorePosition)?;
+		self.drawn_images = wanted;
+
+		out.flush()
+	}
+
+	fn place_links(&mut self, rect: Rect, top: usize) {
+		// find where each visible link was drawn, so that we can go back over
+		// it with a hyperlink once tui has drawn everything
+		let inner_width = rect.width.saturating_sub(2) as usize;
+		let inner_height = rect.height.saturating_sub(2) as usize;
+
+		self.visible_links = self
+			.line_list
+			.iter()
+			.enumerate()
+			.skip(top)
+			.take(inner_height)
+			.filter(|(_, l)| matches!(l.message_type, MessageLineType::Text))
+			.flat_map(|(i, l)| {
+				Utilities::find_urls(&l.text)
+					.into_iter()
+					.filter_map(|(start, end)| {
+						let offset = UnicodeWidthStr::width(&l.text[..start]);
+
+						if offset >= inner_width {
+							return None;
+						}
+
+						// only go over the part of the link that could fit
+						let mut width = offset;
+						let text = l.text[start..end]
+							.graphemes(true)
+							.take_while(|g| {
+								width += UnicodeWidthStr::width(*g);
+								width <= inner_width
+							})
+							.collect::<String>();
+
+						Some(LinkPlacement {
+							x: rect.x + 1 + offset as u16,
+							y: rect.y + 1 + (i - top) as u16,
+							url: Utilities::url_target(&l.text[start..end]),
+							text,
+						})
+					})
+					.collect::<Vec<LinkPlacement>>()
+			})
+			.collect();
+	}
+
+	pub fn write_hyperlinks<W: Write>(
+		&self,
+		out: &mut W,
+		settings: &Settings,
+	) -> std::io::Result<()> {
+		// tui can't draw hyperlinks itself, so we go back over each link
+		// after it's drawn and wrap it in an OSC 8 escape sequence.
+		// Terminals that don't support them just ignore them.
+		let color = match settings.colorscheme.link {
+			Color::Rgb(r, g, b) => cstyle::Color::Rgb { r, g, b },
+			_ => cstyle::Color::Reset,
+		};
+
+		queue!(out, cursor::SavePosition)?;
+
+		// links come from other people, so anything that could end the escape
+		// sequence early (and start another one) means it's just left as text
+		for link in self
+			.visible_links
+			.iter()
+			.filter(|l| MessagesView::safe_for_escape(&l.url, &l.text))
+		{
+			queue!(
+				out,
+				cursor::MoveTo(link.x, link.y),
+				cstyle::SetForegroundColor(color),
+				cstyle::SetAttribute(cstyle::Attribute::Underlined),
+				cstyle::Print(format!(
+					"\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\",
+					link.url, link.text
+				)),
+				cstyle::SetAttribute(cstyle::Attribute::Reset),
+			)?;
+		}
+
+		queue!(out, cursor::RestorePosition)?;
+		out.flush()
+	}
+
+	fn safe_for_escape(url: &str, text: &str) -> bool {
+		// urls can only be printable ascii, and the text can't have control chars
+		url.bytes().all(|b| (0x20..=0x7e).contains(&b)) && !text.chars().any(char::is_control)
 	}
 
 	pub fn rerender_list(&mut self, rect: Rect, settings: &Settings) {
@@ -121,11 +404,16 @@ impl MessagesView {
 		// and you don't have to call it every single time the view is redrawn.
 
 		let msg_width = rect.width as usize - 2;
-		let opts = textwrap::Options::new((msg_width as f64 * 0.6) as usize);
+		let wrap_width = (msg_width as f64 * 0.6) as usize;
 
 		let mut last_timestamp = 0;
 		let mut last_sender = "".to_owned();
 		let mut att_temp = Vec::new();
+		let mut link_temp = Vec::new();
+		let mut block_temp = HashMap::new();
+
+		// they could have changed the setting since the last time
+		self.graphics = GraphicsProtocol::from_setting(&settings.inline_images);
 
 		// This gets a vector of spans for all the messages. It handles stuff like
 		// inserting the time when necessary, adding the underlines, splitting the
@@ -155,7 +443,7 @@ impl MessagesView {
 						}
 
 						vec.push(MessageLine::new(
-							send.to_owned(),
+							NameCache::aliased(send),
 							MessageLineType::Sender,
 							i,
 							msg.is_from_me,
@@ -168,21 +456,28 @@ impl MessagesView {
 				last_timestamp = msg.date;
 
 				// split the text into its wrapped lines
-				let text_lines: Vec<String> = textwrap::fill(msg.text.as_str(), opts.clone())
-					.split('\n')
-					.map(|l| l.to_owned())
-					.collect();
+				let first_link = link_temp.len();
+				let text = MessagesView::label_links(&msg.text, &mut link_temp);
+				let text_lines = Utilities::wrap_text(&text, wrap_width);
+
+				// and the previews for its links, to go under the text
+				let preview_lines = self.preview_lines(&link_temp[first_link..], wrap_width);
 
 				// find the length of the longest line
-				// (length calculated by utf-8 chars)
-				let mut max = text_lines.iter().fold(0, |m, l| {
-					let len = UnicodeWidthStr::width(l.as_str());
-					if len > m {
-						len
-					} else {
-						m
-					}
-				});
+				// (length calculated by utf-8 chars). Long links can make
+				// lines wider than the view, so make sure it fits.
+				let mut max = text_lines
+					.iter()
+					.chain(preview_lines.iter())
+					.fold(0, |m, l| {
+						let len = UnicodeWidthStr::width(l.as_str());
+						if len > m {
+							len
+						} else {
+							m
+						}
+					});
+				max = min(max, msg_width);
 
 				// do attachments
 				for att in msg.attachments.iter() {
@@ -207,6 +502,27 @@ impl MessagesView {
 						i,
 						msg.is_from_me,
 					));
+
+					// and show the thumbnail under it, if it's an image
+					if let Some(blocks) =
+						self.thumbnail_blocks(&att.path, &att.mime_type, wrap_width, settings)
+					{
+						let mut lines = MessagesView::image_lines(
+							&blocks,
+							att_temp.len(),
+							i,
+							msg.is_from_me,
+							msg_width,
+						);
+
+						if blocks[0].len() > max {
+							max = blocks[0].len();
+						}
+
+						vec.append(&mut lines);
+						block_temp.insert(att.path.to_owned(), blocks);
+					}
+
 					att_temp.push(att.path.to_owned());
 				}
 
@@ -216,14 +532,20 @@ impl MessagesView {
 					// add padding for my texts, put into spans
 					let mut lines: Vec<MessageLine> = text_lines
 						.into_iter()
-						.map(|l| {
+						.map(|l| (l, MessageLineType::Text))
+						.chain(
+							preview_lines
+								.into_iter()
+								.map(|l| (l, MessageLineType::Preview)),
+						)
+						.map(|(l, line_type)| {
 							let text = if msg.is_from_me {
 								format!("{}{}", " ".repeat(space), l)
 							} else {
 								l
 							};
 
-							MessageLine::new(text, MessageLineType::Text, i, msg.is_from_me)
+							MessageLine::new(text, line_type, i, msg.is_from_me)
 						})
 						.collect();
 
@@ -251,9 +573,13 @@ impl MessagesView {
 				vec
 			});
 
-		// have to have a stored vector of attachments
+		// have to have a stored vector of attachments (and links)
 		// so that you can access and open them at will
 		self.attachments = att_temp;
+		self.links = link_temp;
+		self.image_blocks = block_temp;
+
+		self.request_previews(settings);
 
 		// y_bounds are what are shown
 		if self.line_list.len() as u16 >= rect.height {
@@ -415,7 +741,7 @@ impl MessagesView {
 				}
 
 				self.line_list.push(MessageLine::new(
-					send.to_owned(),
+					NameCache::aliased(send),
 					MessageLineType::Sender,
 					i,
 					msg.is_from_me,
@@ -423,23 +749,30 @@ impl MessagesView {
 			}
 		}
 
-		let opts = textwrap::Options::new(((self.last_width - 2) as f64 * 0.6) as usize);
+		let wrap_width = ((self.last_width - 2) as f64 * 0.6) as usize;
 
 		// split the text into its wrapped lines
-		let text_lines: Vec<String> = textwrap::fill(msg.text.as_str(), opts)
-			.split('\n')
-			.map(|l| l.to_owned())
-			.collect();
+		let first_link = self.links.len();
+		let text = MessagesView::label_links(&msg.text, &mut self.links);
+		let text_lines = Utilities::wrap_text(&text, wrap_width);
+
+		// and the previews for its links, to go under the text
+		let preview_lines = self.preview_lines(&self.links[first_link..], wrap_width);
+		self.request_previews(settings);
 
 		// find the length of the longest line (length calculated by utf-8 chars)
-		let mut max = text_lines.iter().fold(0, |m, l| {
-			let len = UnicodeWidthStr::width(l.as_str());
-			if len > m {
-				len
-			} else {
-				m
-			}
-		});
+		let mut max = text_lines
+			.iter()
+			.chain(preview_lines.iter())
+			.fold(0, |m, l| {
+				let len = UnicodeWidthStr::width(l.as_str());
+				if len > m {
+					len
+				} else {
+					m
+				}
+			});
+		max = min(max, self.last_width as usize - 2);
 
 		// do attachments
 		for att in msg.attachments.iter() {
@@ -462,6 +795,27 @@ impl MessagesView {
 				i,
 				msg.is_from_me,
 			));
+
+			// and show the thumbnail under it, if it's an image
+			if let Some(blocks) =
+				self.thumbnail_blocks(&att.path, &att.mime_type, wrap_width, settings)
+			{
+				let mut lines = MessagesView::image_lines(
+					&blocks,
+					self.attachments.len(),
+					i,
+					msg.is_from_me,
+					self.last_width as usize - 2,
+				);
+
+				if blocks[0].len() > max {
+					max = blocks[0].len();
+				}
+
+				self.line_list.append(&mut lines);
+				self.image_blocks.insert(att.path.to_owned(), blocks);
+			}
+
 			self.attachments.push(att.path.to_owned());
 		}
 
@@ -471,14 +825,20 @@ impl MessagesView {
 		if !msg.text.is_empty() {
 			let mut lines: Vec<MessageLine> = text_lines
 				.into_iter()
-				.map(|l| {
+				.map(|l| (l, MessageLineType::Text))
+				.chain(
+					preview_lines
+						.into_iter()
+						.map(|l| (l, MessageLineType::Preview)),
+				)
+				.map(|(l, line_type)| {
 					let text = if msg.is_from_me {
 						format!("{}{}", " ".repeat(space), l)
 					} else {
 						l
 					};
 
-					MessageLine::new(text, MessageLineType::Text, i, msg.is_from_me)
+					MessageLine::new(text, line_type, i, msg.is_from_me)
 				})
 				.collect();
 
@@ -555,71 +915,248 @@ impl MessagesView {
 		}
 	}
 
-	pub fn open_attachment(&self, idx: usize) {
-		// Download the attachment to their downloads directory
+	fn label_links(text: &str, links: &mut Vec<String>) -> String {
+		// put the index of each link after it, so that they can be opened
+		// with `:o`, and keep track of them in `links`
+		let mut labeled = "".to_owned();
+		let mut last = 0;
 
-		if idx >= self.attachments.len() {
-			hint!(
-				"cannot get attachment {} (there are only {})",
-				idx,
-				self.attachments.len()
-			);
-			return;
+		for (start, end) in Utilities::find_urls(text) {
+			labeled.push_str(&text[last..end]);
+			labeled.push_str(&format!(" [{}]", links.len()));
+
+			links.push(Utilities::url_target(&text[start..end]));
+			last = end;
 		}
 
-		let mut down_dir = match dirs::download_dir() {
-			Some(dir) => dir,
-			None => {
-				let mut dir =
-					dirs::home_dir().expect("Can't get download directory or home directory");
-				dir.push("Downloads");
-				dir
-			}
-		};
+		labeled.push_str(&text[last..]);
+		labeled
+	}
+
+	fn preview_lines(&self, links: &[String], width: usize) -> Vec<String> {
+		// get the lines that show the previews for these links, if they've
+		// been loaded in, like:
+		// ▎ Example Site
+		// ▎ The Title Of The Page
+		// ▎ The description of the page, which can
+		// ▎ go on for up to two lines...
+		let mut lines = Vec::new();
+		let inner_width = width.saturating_sub(2);
+
+		for link in links.iter() {
+			if let Some(Some(prev)) = self.previews.get(link) {
+				lines.push(format!(
+					"▎ {}",
+					Utilities::truncate(&prev.site_name, inner_width)
+				));
+				lines.push(format!(
+					"▎ {}",
+					Utilities::truncate(&prev.title, inner_width)
+				));
 
-		let att_name = self.attachments[idx].to_owned();
+				let desc = Utilities::wrap_text(&prev.description, inner_width);
 
-		let location = match att_name.split('/').last() {
-			Some(name) => {
-				down_dir.push(name);
-				match down_dir.as_os_str().to_str() {
-					Some(file_loc) => {
-						hint!("Downloading file to {}", file_loc);
-						Some(file_loc.to_owned())
-					}
-					None => {
-						hint!(
-							"Cannot download file. Please ensure the filename \
-							({}) is valid unicode",
-							name
-						);
-						None
-					}
+				for (i, line) in desc.iter().filter(|l| !l.is_empty()).take(2).enumerate() {
+					let line = if i == 1 && desc.len() > 2 {
+						// show that there was more that didn't fit
+						Utilities::truncate(&format!("{}...", line), inner_width)
+					} else {
+						line.to_owned()
+					};
+
+					lines.push(format!("▎ {}", line));
 				}
 			}
-			None => {
-				hint!("cannot get the name of the attachment to download :(");
-				None
+		}
+
+		lines
+	}
+
+	fn request_previews(&mut self, settings: &Settings) {
+		// fetch the previews for all the links that don't have them yet,
+		// if they're allowed to be fetched
+		for link in self.links.iter() {
+			if self.previews.contains_key(link) || !LinkPreview::allowed(link, settings) {
+				continue;
 			}
+
+			// so that it's not fetched twice while it's loading
+			self.previews.insert(link.to_owned(), None);
+
+			let url = link.to_owned();
+			let timeout = settings.timeout;
+			let allow = settings.preview_allow.to_owned();
+			let deny = settings.preview_deny.to_owned();
+
+			tokio::spawn(async move {
+				let prev = LinkPreview::fetch(&url, timeout, &allow, &deny).await.ok();
+
+				if let Ok(mut state) = STATE.write() {
+					state.new_previews.push((url, prev));
+				}
+			});
+		}
+	}
+
+	pub fn add_previews(&mut self, previews: Vec<(String, Option<LinkPreview>)>) {
+		let any_loaded = previews.iter().any(|(_, p)| p.is_some());
+
+		for (url, prev) in previews.into_iter() {
+			self.previews.insert(url, prev);
+		}
+
+		// only save and redraw if there's actually something new to show
+		if any_loaded {
+			LinkPreview::save_cache(&self.previews);
+
+			// kinda dirty trick to force it to redraw the list next time
+			self.last_height = 0;
+		}
+	}
+
+	fn thumbnail_blocks(
+		&self,
+		path: &str,
+		mime_type: &str,
+		width: usize,
+		settings: &Settings,
+	) -> Option<ImageBlocks> {
+		// get the thumbnail of an attachment as blocks, if it's an image
+		// whose thumbnail has been loaded in and they want to see images
+		if self.graphics.is_none() || !Graphics::is_image(mime_type) {
+			return None;
+		}
+
+		let thumb = self.thumbnails.get(path)?.as_ref()?;
+		let blocks = Graphics::blocks(thumb, settings.image_height, width as u16);
+
+		if blocks.is_empty() {
+			None
+		} else {
+			Some(blocks)
+		}
+	}
+
+	fn image_lines(
+		blocks: &ImageBlocks,
+		att: usize,
+		i: usize,
+		from_me: bool,
+		msg_width: usize,
+	) -> Vec<MessageLine> {
+		// the lines for an image are just filled with spaces, and are
+		// replaced with the actual image when they're drawn
+		let cols = blocks[0].len();
+		let space = if from_me {
+			msg_width - cols
+		} else {
+			0
+		};
+
+		(0..blocks.len())
+			.map(|row| {
+				MessageLine::new(
+					" ".repeat(space + cols),
+					MessageLineType::Image(att, row as u16),
+					i,
+					from_me,
+				)
+			})
+			.collect()
+	}
+
+	fn request_thumbnails(&mut self, top: usize, height: usize) {
+		// load the thumbnails for the image attachments in the texts that
+		// are showing, if they don't have them yet and want to see them
+		if self.graphics.is_none() {
+			return;
+		}
+
+		let showing = self.line_list.iter().skip(top).take(height);
+		let (first, last) = match (
+			showing.clone().map(|l| l.relative_index).min(),
+			showing.map(|l| l.relative_index).max(),
+		) {
+			(Some(first), Some(last)) => (first, last),
+			_ => return,
 		};
 
-		if let Some(loc) = location {
+		let images = self
+			.messages
+			.iter()
+			.skip(first)
+			.take(last + 1 - first)
+			.flat_map(|m| m.attachments.iter())
+			.filter(|a| Graphics::is_image(&a.mime_type) && !self.thumbnails.contains_key(&a.path))
+			.map(|a| a.path.to_owned())
+			.collect::<Vec<String>>();
+
+		for path in images.into_iter() {
+			// so that it's not loaded twice while it's loading
+			self.thumbnails.insert(path.to_owned(), None);
+
 			let api_clone = self.client.clone();
 
 			tokio::spawn(async move {
-				let mut api = api_clone.write().await;
+				let thumb = Graphics::load_thumbnail(api_clone, path.to_owned()).await;
 
-				match api.get_attachment(&att_name).await {
-					Ok(data) => match std::fs::write(&loc, data) {
-						Ok(_) => hint!("downloaded file to {} :)", loc),
-						Err(err) => hint!("Could not write to file at {}: {}", loc, err),
-					},
-					Err(err) => hint!("could not download file: {}", err),
+				if let Ok(mut state) = STATE.write() {
+					state.new_thumbnails.push((path, thumb));
 				}
 			});
 		}
 	}
 
+	pub fn add_thumbnails(&mut self, thumbnails: Vec<(String, Option<RgbaImage>)>) {
+		let any_loaded = thumbnails.iter().any(|(_, t)| t.is_some());
+
+		for (path, thumb) in thumbnails.into_iter() {
+			self.thumbnails.insert(path, thumb);
+		}
+
+		if any_loaded {
+			self.last_height = 0;
+		}
+	}
+
+	pub fn open_link(&self, idx: usize, settings: &Settings) {
+		match self.links.get(idx) {
+			Some(link) => Utilities::open_with_system(link, &settings.link_opener),
+			None => hint!(
+				"cannot open link {} (there are only {})",
+				idx,
+				self.links.len()
+			),
+		}
+	}
+
+	pub fn download_attachment(&self, idx: usize, dir: PathBuf, settings: &Settings) {
+		// Download the attachment to their downloads directory,
+		// and open it afterwards if they want that
+
+		if idx >= self.attachments.len() {
+			hint!(
+				"cannot get attachment {} (there are only {})",
+				idx,
+				self.attachments.len()
+			);
+			return;
+		}
+
+		let open_with = if settings.open_downloads {
+			Some(settings.link_opener.to_owned())
+		} else {
+			None
+		};
+
+		AttachmentsView::download(
+			self.client.clone(),
+			vec![self.attachments[idx].to_owned()],
+			dir,
+			open_with,
+		);
+	}
+
 	pub async fn delete_current_text(&mut self) -> bool {
 		// deletes the currently selected text
 
@@ -668,3 +1205,43 @@ impl MessagesView {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn escapes_are_not_hyperlinked() {
+		assert!(MessagesView::safe_for_escape(
+			"https://example.com/a?b=c&d",
+			"example.com"
+		));
+
+		// ESC, BEL, and anything else that could end the OSC 8 sequence
+		assert!(!MessagesView::safe_for_escape(
+			"https://x.com/\x1b\\\x1b]0;hi\x07",
+			"x.com"
+		));
+		assert!(!MessagesView::safe_for_escape(
+			"https://x.com/\x07",
+			"x.com"
+		));
+		assert!(!MessagesView::safe_for_escape(
+			"https://x.com/\u{9b}",
+			"x.com"
+		));
+		assert!(!MessagesView::safe_for_escape("https://x.com/\n", "x.com"));
+		assert!(!MessagesView::safe_for_escape(
+			"https://x.com/\x7f",
+			"x.com"
+		));
+		assert!(!MessagesView::safe_for_escape(
+			"https://exämple.com",
+			"exämple.com"
+		));
+		assert!(!MessagesView::safe_for_escape(
+			"https://x.com",
+			"x.com\x1b[2J"
+		));
+	}
+}
@@ -37,4 +37,26 @@ pub enum MessageLineType {
 	Sender,
 	Underline,
 	Typing,
+	Preview,
+	// a row of an image attachment's thumbnail; (attachment index, row)
+	Image(usize, u16),
+}
+
+// where a link was drawn on the screen, so that it can be made clickable
+pub struct LinkPlacement {
+	pub x: u16,
+	pub y: u16,
+	pub text: String,
+	pub url: String,
+}
+
+// where an image attachment was drawn, so that it can be drawn over
+// with a graphics protocol once tui has drawn everything
+#[derive(Clone, PartialEq)]
+pub struct ImagePlacement {
+	pub x: u16,
+	pub y: u16,
+	pub cols: u16,
+	pub rows: u16,
+	pub attachment: usize,
 }
@@ -0,0 +1,213 @@
+use crate::{utilities::Utilities, *};
+use chrono::Local;
+use sdk::api::APIClient;
+use std::{collections::HashMap, path::PathBuf};
+use tokio::sync::RwLock;
+
+// how long a name is trusted for before it's looked up again, in seconds.
+// People don't change their contacts much, so a day seems fine
+const NAME_TTL: i64 = 60 * 60 * 24;
+
+pub struct NameCache {
+	// the name for each address or chat identifier, and when it was looked up
+	names: HashMap<String, (String, i64)>,
+	// the nicknames they've given addresses and chats, from `contacts.toml` and
+	// `aliases.toml`. These always win over what the server says
+	pub aliases: HashMap<String, String>,
+	// the ones set with `:alias`. They're kept in their own file so that
+	// `contacts.toml` is never rewritten, and an empty one means that the
+	// nickname from `contacts.toml` was taken away
+	set_aliases: HashMap<String, String>,
+}
+
+impl NameCache {
+	pub fn load() -> NameCache {
+		// names that were looked up in previous sessions are kept too,
+		// as long as they haven't gotten too old
+		let now = Local::now().timestamp();
+
+		let names = match Utilities::read_toml(&NameCache::cache_file()) {
+			Some(table) => table
+				.into_iter()
+				.filter_map(|(id, val)| {
+					let name = val.get("name")?.as_str()?.to_owned();
+					let fetched = val.get("fetched")?.as_integer()?;
+
+					if now - fetched < NAME_TTL {
+						Some((id, (name, fetched)))
+					} else {
+						None
+					}
+				})
+				.collect(),
+			None => HashMap::new(),
+		};
+
+		let read_aliases = |file: PathBuf| -> HashMap<String, String> {
+			match Utilities::read_toml(&file) {
+				Some(table) => table
+					.into_iter()
+					.filter_map(|(id, alias)| Some((id, alias.as_str()?.to_owned())))
+					.collect(),
+				None => HashMap::new(),
+			}
+		};
+
+		let mut cache = NameCache {
+			names,
+			aliases: read_aliases(NameCache::contacts_file()),
+			set_aliases: read_aliases(NameCache::aliases_file()),
+		};
+
+		for (id, alias) in cache.set_aliases.clone() {
+			cache.apply_alias(&id, &alias);
+		}
+
+		cache
+	}
+
+	fn apply_alias(&mut self, id: &str, alias: &str) {
+		if alias.is_empty() {
+			self.aliases.remove(id);
+		} else {
+			self.aliases.insert(id.to_owned(), alias.to_owned());
+		}
+	}
+
+	pub fn aliased(name: &str) -> String {
+		// the nickname they've given this address (or name), if they've given it one
+		match read_state!().names.aliases.get(name) {
+			Some(alias) => alias.to_owned(),
+			None => name.to_owned(),
+		}
+	}
+
+	pub fn unaliased(addresses: &str) -> String {
+		// so that they can type nicknames in the compose address box, and
+		// they're sent to the right place
+		let aliases = &read_state!().names.aliases;
+
+		addresses
+			.split(',')
+			.map(|a| {
+				let a = a.trim();
+				aliases
+					.iter()
+					.find(|(_, alias)| alias.eq_ignore_ascii_case(a))
+					.map(|(id, _)| id.to_owned())
+					.unwrap_or_else(|| a.to_owned())
+			})
+			.collect::<Vec<String>>()
+			.join(",")
+	}
+
+	pub fn set_alias(&mut self, id: &str, alias: Option<&str>) {
+		let alias = alias.unwrap_or_default();
+
+		self.apply_alias(id, alias);
+		self.set_aliases.insert(id.to_owned(), alias.to_owned());
+
+		let table = self
+			.set_aliases
+			.iter()
+			.map(|(id, alias)| (id.to_owned(), alias.to_owned().into()))
+			.collect();
+
+		Utilities::write_toml(&NameCache::aliases_file(), table);
+	}
+
+	pub fn known(&self) -> Vec<(String, String)> {
+		// all the addresses that we know the names for
+		let now = Local::now().timestamp();
+
+		self.names
+			.iter()
+			.filter(|(_, (_, fetched))| now - fetched < NAME_TTL)
+			.map(|(id, (name, _))| (id.to_owned(), name.to_owned()))
+			.collect()
+	}
+
+	pub fn get(&self, id: &str) -> Option<String> {
+		let (name, fetched) = self.names.get(id)?;
+
+		if Local::now().timestamp() - fetched < NAME_TTL {
+			Some(name.to_owned())
+		} else {
+			None
+		}
+	}
+
+	pub fn insert(&mut self, id: &str, name: &str) {
+		self.names
+			.insert(id.to_owned(), (name.to_owned(), Local::now().timestamp()));
+		self.save();
+	}
+
+	pub fn clear(&mut self) {
+		self.names.clear();
+		self.save();
+	}
+
+	pub async fn get_name(client: &Arc<RwLock<APIClient>>, id: &str) -> anyhow::Result<String> {
+		// check their nicknames and the cache first, so that we don't have to
+		// wait on the client (and the server) for every text that comes in
+		let known = {
+			let state = read_state!();
+			state
+				.names
+				.aliases
+				.get(id)
+				.cloned()
+				.or_else(|| state.names.get(id))
+		};
+
+		if let Some(name) = known {
+			return Ok(name);
+		}
+
+		let mut api = client.write().await;
+		let name = api.get_name(id).await?;
+		drop(api);
+
+		if let Ok(mut state) = STATE.write() {
+			state.names.insert(id, &name);
+		}
+
+		Ok(name)
+	}
+
+	fn save(&self) {
+		let table = self
+			.names
+			.iter()
+			.map(|(id, (name, fetched))| {
+				let mut entry = toml::value::Table::new();
+
+				entry.insert("name".to_owned(), name.to_owned().into());
+				entry.insert("fetched".to_owned(), (*fetched).into());
+
+				(id.to_owned(), toml::Value::Table(entry))
+			})
+			.collect();
+
+		Utilities::write_toml(&NameCache::cache_file(), table);
+	}
+
+	fn contacts_file() -> PathBuf {
+		let mut file = config_dir();
+		file.push("contacts.toml");
+		file
+	}
+
+	fn aliases_file() -> PathBuf {
+		let mut file = config_dir();
+		file.push("aliases.toml");
+		file
+	}
+
+	fn cache_file() -> PathBuf {
+		let mut file = cache_dir();
+		file.push("names.toml");
+		file
+	}
+}
@@ -0,0 +1,321 @@
+use crate::utilities::Utilities;
+use clipboard::{ClipboardContext, ClipboardProvider};
+use std::{
+	fs::File,
+	io::{Read, Write},
+	path::Path,
+	process::{Command, Stdio},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+// what was pasted, ready to be sent
+pub struct Pasted {
+	pub files: Vec<String>,
+	// e.g. `an image (image/png, 1.2 MB)`, to show them what they're sending
+	pub description: String,
+	// if the files were made just for this, so they should be deleted once it's sent
+	pub temporary: bool,
+}
+
+pub struct Paste;
+
+impl Paste {
+	pub fn from_clipboard() -> anyhow::Result<Pasted> {
+		// images are checked first, since copying an image in some apps
+		// puts its name (or something else useless) in as text, too
+		if let Some(data) = Paste::clipboard_image() {
+			let (mime_type, ext) =
+				Paste::detect_type(&data).ok_or_else(|| anyhow::anyhow!("unknown image type"))?;
+
+			let time = SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map(|d| d.as_millis())
+				.unwrap_or(0);
+
+			let (file, mut handle) =
+				Utilities::create_private_file(&format!("pasted-{}.{}", time, ext))?;
+
+			if let Err(err) = handle.write_all(&data) {
+				let _ = std::fs::remove_file(&file);
+				return Err(err.into());
+			}
+
+			return Ok(Pasted {
+				files: vec![file.to_string_lossy().to_string()],
+				description: format!(
+					"an image ({}, {})",
+					mime_type,
+					Utilities::format_size(data.len())
+				),
+				temporary: true,
+			});
+		}
+
+		let mut ctx: ClipboardContext =
+			ClipboardProvider::new().map_err(|err| anyhow::anyhow!("{}", err))?;
+		let text = ctx
+			.get_contents()
+			.map_err(|err| anyhow::anyhow!("{}", err))?;
+
+		let files = Paste::parse_file_list(&text);
+
+		if files.is_empty() {
+			return Err(anyhow::anyhow!(
+				"the clipboard doesn't have an image or any files in it"
+			));
+		}
+
+		let size = files
+			.iter()
+			.filter_map(|f| std::fs::metadata(f).ok())
+			.map(|m| m.len() as usize)
+			.sum();
+
+		let description = if files.len() == 1 {
+			let name = files[0].rsplit(['/', '\\']).next().unwrap_or_default();
+			let mime_type = Paste::file_type(&files[0]).unwrap_or("unknown type");
+
+			format!("{} ({}, {})", name, mime_type, Utilities::format_size(size))
+		} else {
+			format!("{} files ({})", files.len(), Utilities::format_size(size))
+		};
+
+		Ok(Pasted {
+			files,
+			description,
+			temporary: false,
+		})
+	}
+
+	fn clipboard_image() -> Option<Vec<u8>> {
+		// the clipboard crate can only get text, so we have to ask whatever
+		// manages the clipboard on their system for images
+		if cfg!(target_os = "macos") {
+			return Paste::run("pngpaste", &["-"]);
+		}
+
+		if cfg!(windows) {
+			return None;
+		}
+
+		// the clipboard can have the same thing in a bunch of types,
+		// so find which image types it has, preferring png
+		let wayland = std::env::var("WAYLAND_DISPLAY").is_ok();
+
+		let types = if wayland {
+			Paste::run("wl-paste", &["--list-types"])?
+		} else {
+			Paste::run("xclip", &["-selection", "clipboard", "-t", "TARGETS", "-o"])?
+		};
+
+		let types = String::from_utf8_lossy(&types);
+		let images = types
+			.lines()
+			.map(|t| t.trim())
+			.filter(|t| t.starts_with("image/"))
+			.collect::<Vec<&str>>();
+
+		let image_type = images
+			.iter()
+			.find(|t| **t == "image/png")
+			.or_else(|| images.first())?;
+
+		if wayland {
+			Paste::run("wl-paste", &["--no-newline", "--type", image_type])
+		} else {
+			Paste::run(
+				"xclip",
+				&["-selection", "clipboard", "-t", image_type, "-o"],
+			)
+		}
+	}
+
+	fn run(cmd: &str, args: &[&str]) -> Option<Vec<u8>> {
+		// run a command and get what it prints, if it worked
+		let output = Command::new(cmd)
+			.args(args)
+			.stdin(Stdio::null())
+			.stderr(Stdio::null())
+			.output()
+			.ok()?;
+
+		if output.status.success() && !output.stdout.is_empty() {
+			Some(output.stdout)
+		} else {
+			None
+		}
+	}
+
+	fn parse_file_list(text: &str) -> Vec<String> {
+		// file managers copy files as a list of paths or `file://` urls, one on
+		// each line. Some of them (like nautilus) put `copy` or `cut` first.
+		let lines = text
+			.lines()
+			.map(|l| l.trim())
+			.filter(|l| !l.is_empty() && *l != "copy" && *l != "cut")
+			.map(|l| match l.strip_prefix("file://") {
+				Some(path) => Paste::decode_url(path),
+				None => l.to_owned(),
+			})
+			.collect::<Vec<String>>();
+
+		// if any of them aren't files, it's probably just text
+		if lines.iter().all(|l| Path::new(l).is_file()) {
+			lines
+		} else {
+			Vec::new()
+		}
+	}
+
+	fn decode_url(path: &str) -> String {
+		// decode the percent-encoded characters in a `file://` url, like `%20`
+		let bytes = path.as_bytes();
+		let mut decoded = Vec::new();
+		let mut i = 0;
+
+		while i < bytes.len() {
+			let hex = if bytes[i] == b'%' && i + 2 < bytes.len() {
+				std::str::from_utf8(&bytes[i + 1..i + 3])
+					.ok()
+					.and_then(|h| u8::from_str_radix(h, 16).ok())
+			} else {
+				None
+			};
+
+			match hex {
+				Some(byte) => {
+					decoded.push(byte);
+					i += 3;
+				}
+				None => {
+					decoded.push(bytes[i]);
+					i += 1;
+				}
+			}
+		}
+
+		String::from_utf8_lossy(&decoded).to_string()
+	}
+
+	fn file_type(path: &str) -> Option<&'static str> {
+		let mut start = [0; 16];
+		let read = File::open(path).ok()?.read(&mut start).ok()?;
+
+		Paste::detect_type(&start[..read]).map(|(mime_type, _)| mime_type)
+	}
+
+	fn detect_type(data: &[u8]) -> Option<(&'static str, &'static str)> {
+		// find the mime type and extension of some data from
+		// the magic bytes at the start of it
+		let starts = |magic: &[u8]| data.starts_with(magic);
+		let brand = data.get(4..12).unwrap_or_default();
+
+		if starts(b"\x89PNG\r\n\x1a\n") {
+			Some(("image/png", "png"))
+		} else if starts(b"\xff\xd8\xff") {
+			Some(("image/jpeg", "jpg"))
+		} else if starts(b"GIF87a") || starts(b"GIF89a") {
+			Some(("image/gif", "gif"))
+		} else if starts(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+			Some(("image/webp", "webp"))
+		} else if brand == b"ftypheic" || brand == b"ftypheix" || brand == b"ftypmif1" {
+			Some(("image/heic", "heic"))
+		} else if starts(b"II*\0") || starts(b"MM\0*") {
+			Some(("image/tiff", "tiff"))
+		} else if starts(b"BM") {
+			Some(("image/bmp", "bmp"))
+		} else if starts(b"%PDF") {
+			Some(("application/pdf", "pdf"))
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decode_url_decodes_percent_escapes() {
+		assert_eq!(
+			Paste::decode_url("/home/me/My%20File.txt"),
+			"/home/me/My File.txt"
+		);
+		assert_eq!(Paste::decode_url("/caf%C3%A9"), "/café");
+		assert_eq!(Paste::decode_url("/100%25"), "/100%");
+
+		// things that aren't escapes are left alone
+		assert_eq!(Paste::decode_url("/a%zz"), "/a%zz");
+		assert_eq!(Paste::decode_url("/a%2"), "/a%2");
+		assert_eq!(Paste::decode_url("/a%"), "/a%");
+	}
+
+	#[test]
+	fn detect_type_uses_magic_bytes() {
+		assert_eq!(
+			Paste::detect_type(b"\x89PNG\r\n\x1a\n...."),
+			Some(("image/png", "png"))
+		);
+		assert_eq!(
+			Paste::detect_type(b"\xff\xd8\xff\xe0"),
+			Some(("image/jpeg", "jpg"))
+		);
+		assert_eq!(Paste::detect_type(b"GIF89a"), Some(("image/gif", "gif")));
+		assert_eq!(
+			Paste::detect_type(b"RIFF\0\0\0\0WEBPVP8 "),
+			Some(("image/webp", "webp"))
+		);
+		assert_eq!(
+			Paste::detect_type(b"\0\0\0\x18ftypheic"),
+			Some(("image/heic", "heic"))
+		);
+		assert_eq!(
+			Paste::detect_type(b"%PDF-1.7"),
+			Some(("application/pdf", "pdf"))
+		);
+
+		assert_eq!(Paste::detect_type(b""), None);
+		assert_eq!(Paste::detect_type(b"RIFF\0\0\0\0WAVE"), None);
+		assert_eq!(Paste::detect_type(b"hello there"), None);
+	}
+
+	#[test]
+	fn parse_file_list_reads_paths_and_urls() {
+		let mut dir = std::env::temp_dir();
+		dir.push(format!("smcurser-paste-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let plain = dir.join("plain.txt");
+		let spaced = dir.join("with space.png");
+		std::fs::write(&plain, "").unwrap();
+		std::fs::write(&spaced, "").unwrap();
+
+		let plain = plain.to_string_lossy().to_string();
+		let spaced = spaced.to_string_lossy().to_string();
+
+		// nautilus-style, with `copy` first and urls after
+		let copied = format!(
+			"copy\nfile://{}\nfile://{}\n",
+			plain,
+			spaced.replace(' ', "%20")
+		);
+		assert_eq!(
+			Paste::parse_file_list(&copied),
+			[plain.clone(), spaced.clone()]
+		);
+
+		// plain paths work too
+		assert_eq!(
+			Paste::parse_file_list(&format!("  {}  ", plain)),
+			vec![plain.clone()]
+		);
+
+		// if anything isn't a file, it's just text
+		assert!(Paste::parse_file_list(&format!("{}\nhello", plain)).is_empty());
+		assert!(Paste::parse_file_list("just some text").is_empty());
+		assert!(Paste::parse_file_list(&dir.to_string_lossy()).is_empty());
+
+		let _ = std::fs::remove_dir_all(&dir);
+	}
+}
@@ -0,0 +1,435 @@
+use crate::{utilities::Utilities, *};
+use chrono::Local;
+use core::time::Duration;
+use reqwest::{
+	header::{CONTENT_TYPE, USER_AGENT},
+	redirect::Policy,
+};
+use std::{cmp::Reverse, collections::HashMap};
+
+// we only look at the start of pages, since everything we need
+// for a preview should be in the `<head>`
+const MAX_PREVIEW_BYTES: usize = 256 * 1024;
+
+// how many previews are kept on disk between sessions. The oldest go first
+const MAX_CACHED_PREVIEWS: usize = 500;
+
+#[derive(Clone)]
+pub struct LinkPreview {
+	pub title: String,
+	pub site_name: String,
+	pub description: String,
+	// when it was fetched, so that the cache can drop the oldest ones
+	pub fetched: i64,
+}
+
+impl LinkPreview {
+	pub fn allowed(url: &str, settings: &Settings) -> bool {
+		// nothing gets fetched unless they've turned previews on, and then the
+		// domain has to not be denied, and has to be allowed if they've
+		// specified which domains to allow.
+		settings.link_previews
+			&& LinkPreview::domain_allowed(url, &settings.preview_allow, &settings.preview_deny)
+	}
+
+	fn domain_allowed(url: &str, allow: &str, deny: &str) -> bool {
+		let host = LinkPreview::host(url);
+
+		let matches = |list: &str| {
+			list.split(',')
+				.map(|d| d.trim().to_lowercase())
+				.filter(|d| !d.is_empty())
+				.any(|d| host == d || host.ends_with(&format!(".{}", d)))
+		};
+
+		!matches(deny) && (allow.trim().is_empty() || matches(allow))
+	}
+
+	pub fn host(url: &str) -> String {
+		// get the domain from the url, e.g. `www.example.com` from
+		// `https://www.example.com:80/path?query`
+		let without_scheme = url.split("://").nth(1).unwrap_or(url);
+
+		without_scheme
+			.split(['/', '?', '#'])
+			.next()
+			.unwrap_or_default()
+			.rsplit('@')
+			.next()
+			.unwrap_or_default()
+			.split(':')
+			.next()
+			.unwrap_or_default()
+			.to_lowercase()
+	}
+
+	pub async fn fetch(
+		url: &str,
+		timeout: u16,
+		allow: &str,
+		deny: &str,
+	) -> anyhow::Result<LinkPreview> {
+		// redirects have to be allowed too, or else an allowed site
+		// could just send us off to one that they didn't agree to
+		let (allow, deny) = (allow.to_owned(), deny.to_owned());
+
+		let policy = Policy::custom(move |attempt| {
+			if attempt.previous().len() >= 10 {
+				attempt.error("too many redirects")
+			} else if LinkPreview::domain_allowed(attempt.url().as_str(), &allow, &deny) {
+				attempt.follow()
+			} else {
+				let err = format!("not fetching previews from {}", attempt.url());
+				attempt.error(err)
+			}
+		});
+
+		let client = reqwest::Client::builder()
+			.timeout(Duration::from_secs(timeout as u64))
+			.redirect(policy)
+			.build()?;
+
+		let mut resp = client
+			.get(url)
+			.header(USER_AGENT, "SMCurser")
+			.send()
+			.await?
+			.error_for_status()?;
+
+		// only web pages can have previews
+		let is_html = resp
+			.headers()
+			.get(CONTENT_TYPE)
+			.and_then(|c| c.to_str().ok())
+			.map(|c| c.contains("text/html"))
+			.unwrap_or(false);
+
+		if !is_html {
+			return Err(anyhow::anyhow!("{} is not a web page", url));
+		}
+
+		let mut body = Vec::new();
+
+		while let Some(chunk) = resp.chunk().await? {
+			body.extend_from_slice(&chunk);
+
+			if body.len() > MAX_PREVIEW_BYTES {
+				break;
+			}
+		}
+
+		let html = String::from_utf8_lossy(&body);
+
+		let mut preview = LinkPreview::parse(&html, url)
+			.ok_or_else(|| anyhow::anyhow!("{} has no preview", url))?;
+
+		preview.fetched = Local::now().timestamp();
+		Ok(preview)
+	}
+
+	pub fn parse(html: &str, url: &str) -> Option<LinkPreview> {
+		// this is definitely not a real html parser, but it's good enough
+		// to pull the open graph tags and title out of a page
+		let lower = html.to_ascii_lowercase();
+		let mut meta: HashMap<String, String> = HashMap::new();
+
+		let mut search = 0;
+		while let Some(pos) = lower[search..].find("<meta") {
+			let start = search + pos;
+			let end = match lower[start..].find('>') {
+				Some(end) => start + end,
+				None => break,
+			};
+
+			let tag = &html[start..end];
+			let key = LinkPreview::attr(tag, "property").or_else(|| LinkPreview::attr(tag, "name"));
+
+			if let (Some(key), Some(content)) = (key, LinkPreview::attr(tag, "content")) {
+				meta.entry(key.to_lowercase()).or_insert(content);
+			}
+
+			search = end;
+		}
+
+		let title_tag = lower.find("<title").and_then(|start| {
+			let open_end = start + lower[start..].find('>')? + 1;
+			let close = open_end + lower[open_end..].find("</title")?;
+			Some(LinkPreview::decode(html[open_end..close].trim()))
+		});
+
+		let title = meta.remove("og:title").or(title_tag)?;
+
+		if title.is_empty() {
+			return None;
+		}
+
+		Some(LinkPreview {
+			title,
+			site_name: meta
+				.remove("og:site_name")
+				.unwrap_or_else(|| LinkPreview::host(url)),
+			description: meta
+				.remove("og:description")
+				.or_else(|| meta.remove("description"))
+				.unwrap_or_default(),
+			fetched: 0,
+		})
+	}
+
+	fn attr(tag: &str, name: &str) -> Option<String> {
+		// get the value of an attribute, e.g. `content="hello"` from
+		// the inside of a tag, quoted with either type of quote
+		let lower = tag.to_ascii_lowercase();
+		let mut search = 0;
+
+		while let Some(pos) = lower[search..].find(name) {
+			let start = search + pos;
+			search = start + name.len();
+
+			// make sure it's the whole attribute name, and not
+			// just the end of another attribute
+			let before = lower[..start].chars().last().unwrap_or(' ');
+			if !before.is_whitespace() {
+				continue;
+			}
+
+			let rest = lower[search..].trim_start();
+			if !rest.starts_with('=') {
+				continue;
+			}
+
+			let value_start = tag.len() - rest[1..].trim_start().len();
+			let quote = tag[value_start..].chars().next()?;
+
+			let value = if quote == '"' || quote == '\'' {
+				let value = &tag[value_start + 1..];
+				&value[..value.find(quote)?]
+			} else {
+				tag[value_start..].split_whitespace().next()?
+			};
+
+			return Some(LinkPreview::decode(value));
+		}
+
+		None
+	}
+
+	fn decode(text: &str) -> String {
+		// decode the html entities that are actually common in titles
+		text.replace("&quot;", "\"")
+			.replace("&#39;", "'")
+			.replace("&#x27;", "'")
+			.replace("&apos;", "'")
+			.replace("&lt;", "<")
+			.replace("&gt;", ">")
+			.replace("&nbsp;", " ")
+			.replace("&amp;", "&")
+	}
+
+	pub fn load_cache() -> HashMap<String, Option<LinkPreview>> {
+		// load in the previews that were fetched in previous sessions
+		let table = match Utilities::read_toml(&LinkPreview::cache_file()) {
+			Some(table) => table,
+			None => return HashMap::new(),
+		};
+
+		table
+			.iter()
+			.filter_map(|(url, val)| {
+				let get = |key: &str| {
+					val.get(key)
+						.and_then(|v| v.as_str())
+						.unwrap_or_default()
+						.to_owned()
+				};
+
+				let preview = LinkPreview {
+					title: get("title"),
+					site_name: get("site_name"),
+					description: get("description"),
+					fetched: val.get("fetched").and_then(|f| f.as_integer()).unwrap_or(0),
+				};
+
+				if preview.title.is_empty() {
+					None
+				} else {
+					Some((url.to_owned(), Some(preview)))
+				}
+			})
+			.collect()
+	}
+
+	pub fn save_cache(previews: &HashMap<String, Option<LinkPreview>>) {
+		// only keep the newest ones, so that the file doesn't grow forever
+		let mut loaded = previews
+			.iter()
+			.filter_map(|(url, prev)| Some((url, prev.as_ref()?)))
+			.collect::<Vec<(&String, &LinkPreview)>>();
+
+		loaded.sort_by_key(|(_, prev)| Reverse(prev.fetched));
+
+		let table = loaded
+			.into_iter()
+			.take(MAX_CACHED_PREVIEWS)
+			.map(|(url, prev)| {
+				let mut entry = toml::value::Table::new();
+
+				entry.insert("title".to_owned(), prev.title.to_owned().into());
+				entry.insert("site_name".to_owned(), prev.site_name.to_owned().into());
+				entry.insert("description".to_owned(), prev.description.to_owned().into());
+				entry.insert("fetched".to_owned(), prev.fetched.into());
+
+				(url.to_owned(), toml::Value::Table(entry))
+			})
+			.collect();
+
+		Utilities::write_toml(&LinkPreview::cache_file(), table);
+	}
+
+	fn cache_file() -> std::path::PathBuf {
+		let mut file = cache_dir();
+		file.push("previews.toml");
+		file
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{
+		io::{Read, Write},
+		net::TcpListener,
+		sync::atomic::{AtomicUsize, Ordering},
+	};
+
+	// a tiny stand-in for a web server that gives the same response to everything
+	// and counts how many requests it got
+	fn serve(response: impl Fn(u16) -> String + Send + 'static) -> (u16, Arc<AtomicUsize>) {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let port = listener.local_addr().unwrap().port();
+		let requests = Arc::new(AtomicUsize::new(0));
+		let counter = requests.clone();
+
+		std::thread::spawn(move || {
+			for mut stream in listener.incoming().flatten() {
+				let mut buf = [0; 4096];
+				let _ = stream.read(&mut buf);
+
+				let path = String::from_utf8_lossy(&buf)
+					.split_whitespace()
+					.nth(1)
+					.unwrap_or_default()
+					.to_owned();
+
+				counter.fetch_add(1, Ordering::SeqCst);
+
+				let resp = if path == "/page" {
+					let body = "<html><head><title>Page</title></head></html>";
+					format!(
+						"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+						body.len(),
+						body
+					)
+				} else {
+					response(port)
+				};
+
+				let _ = stream.write_all(resp.as_bytes());
+			}
+		});
+
+		(port, requests)
+	}
+
+	fn redirect(to: String) -> String {
+		format!(
+			"HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+			to
+		)
+	}
+
+	#[tokio::test]
+	async fn redirect_to_denied_host_is_not_followed() {
+		let (port, requests) = serve(|port| redirect(format!("http://localhost:{}/page", port)));
+		let url = format!("http://127.0.0.1:{}/", port);
+
+		assert!(LinkPreview::fetch(&url, 5, "", "localhost").await.is_err());
+		assert_eq!(requests.load(Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test]
+	async fn redirect_outside_allow_list_is_not_followed() {
+		let (port, requests) = serve(|port| redirect(format!("http://localhost:{}/page", port)));
+		let url = format!("http://127.0.0.1:{}/", port);
+
+		assert!(LinkPreview::fetch(&url, 5, "127.0.0.1", "").await.is_err());
+		assert_eq!(requests.load(Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test]
+	async fn allowed_redirect_is_followed() {
+		let (port, requests) = serve(|port| redirect(format!("http://127.0.0.1:{}/page", port)));
+		let url = format!("http://127.0.0.1:{}/", port);
+
+		let preview = LinkPreview::fetch(&url, 5, "127.0.0.1", "").await.unwrap();
+		assert_eq!(preview.title, "Page");
+		assert_eq!(requests.load(Ordering::SeqCst), 2);
+	}
+
+	#[test]
+	fn domain_lists() {
+		let url = "https://user@www.Example.com:443/path?q=1#frag";
+		assert_eq!(LinkPreview::host(url), "www.example.com");
+
+		assert!(LinkPreview::domain_allowed(url, "", ""));
+		assert!(LinkPreview::domain_allowed(url, "example.com", ""));
+		assert!(!LinkPreview::domain_allowed(url, "other.com", ""));
+		assert!(!LinkPreview::domain_allowed(url, "", " example.com"));
+		assert!(!LinkPreview::domain_allowed(
+			"https://notexample.com",
+			"example.com",
+			""
+		));
+	}
+
+	#[test]
+	fn parse_prefers_open_graph() {
+		let html = r#"<html><head>
+			<title>Plain title</title>
+			<META property="og:title" content="OG &amp; title">
+			<meta property='og:site_name' content='Site'>
+			<meta name="description" content="desc">
+		</head></html>"#;
+
+		let prev = LinkPreview::parse(html, "https://example.com/a").unwrap();
+		assert_eq!(prev.title, "OG & title");
+		assert_eq!(prev.site_name, "Site");
+		assert_eq!(prev.description, "desc");
+	}
+
+	#[test]
+	fn parse_falls_back_to_title_and_host() {
+		let html = "<html><head><title> It&#39;s a page </title></head></html>";
+
+		let prev = LinkPreview::parse(html, "https://www.example.com/a").unwrap();
+		assert_eq!(prev.title, "It's a page");
+		assert_eq!(prev.site_name, "www.example.com");
+		assert_eq!(prev.description, "");
+	}
+
+	#[test]
+	fn parse_ignores_partial_attribute_names() {
+		// `data-name` shouldn't be read as `name`, and unquoted values work
+		let html =
+			r#"<meta data-name="og:title" content="wrong"><meta name=og:title content=right>"#;
+
+		let prev = LinkPreview::parse(html, "https://example.com").unwrap();
+		assert_eq!(prev.title, "right");
+	}
+
+	#[test]
+	fn parse_needs_a_title() {
+		assert!(LinkPreview::parse("<html><body>hi</body></html>", "https://a.com").is_none());
+		assert!(LinkPreview::parse("<title></title>", "https://a.com").is_none());
+	}
+}
@@ -49,6 +49,80 @@ pub fn config_dir() -> PathBuf {
 	conf
 }
 
+pub fn cache_dir() -> PathBuf {
+	match dirs::cache_dir() {
+		Some(mut dir) => {
+			dir.push("smcurser");
+			dir
+		}
+		None => config_dir(),
+	}
+}
+
+// every setting that can be passed as an argument, as (name, short flag, field).
+// Both `parse_args` and `SETTING_NAMES` are made from this, so they can't
+// disagree about what settings there are. The other arguments to this are
+// put in front of the table when it's passed to `$callback`.
+macro_rules! with_settings {
+	($callback:ident $(, $extra:tt)*) => {
+		$callback! {
+			$($extra,)*
+			("rest-host", "-u", rest_host),
+			("fallback-host", "-b", fallback_host),
+			("rest-port", "-p", rest_port),
+			("socket-host", "-o", socket_host),
+			("socket-port", "-w", socket_port),
+			("secure", "-s", secure, flag),
+			("notifications", "-n", notifications, flag),
+			("confirm", "-v", confirm_destructive, flag),
+			("hyperlinks", "-H", hyperlinks, flag),
+			("link-previews", "-P", link_previews, flag),
+			("open-downloads", "-X", open_downloads, flag),
+			("multiline-input", "-M", multiline_input, flag),
+			("vi-mode", "-V", vi_mode, flag),
+			("chat-previews", "-C", chat_previews, flag),
+			("unread-counts", "-K", unread_counts, flag),
+			("password", "-k", password),
+			("chat-indicator", "-x", current_chat_indicator),
+			("unread-indicator", "-z", unread_chat_indicator),
+			("draft-indicator", "-W", draft_chat_indicator),
+			("chat-underline", "-d", chat_underline),
+			("chat-title", "-a", chats_title),
+			("messages-title", "-m", messages_title),
+			("input-title", "-y", input_title),
+			("help-title", "-e", help_title),
+			("to-title", "-q", to_title),
+			("compose-title", "-j", compose_title),
+			("link-opener", "-O", link_opener),
+			("undo-key", "-U", undo_key),
+			("redo-key", "-Y", redo_key),
+			("editor-key", "-E", editor_key),
+			("preview-allow", "-A", preview_allow),
+			("preview-deny", "-D", preview_deny),
+			("inline-images", "-I", inline_images),
+			("image-height", "-R", image_height),
+			("input-max-height", "-T", input_max_height),
+			("history-size", "-N", history_size),
+			("download-dir", "-L", download_dir),
+			("download-subdir", "-S", download_subdir),
+			("poll-input", "-l", poll_input),
+			("theme", "-t", colorscheme),
+			("timeout", "-g", timeout),
+			("remote-url", "-r", remote_url, op),
+			("remote-id", "-i", remote_id, op),
+		}
+	};
+}
+
+macro_rules! setting_names {
+	($(($long:expr, $short:expr, $self:ident $(, $op:ident)?)),* $(,)?) => {
+		// the names of all the settings, so that they can be tab completed with `:b`
+		pub const SETTING_NAMES: &[&str] = &[$($long),*];
+	};
+}
+
+with_settings!(setting_names);
+
 pub struct Settings {
 	pub rest_host: String,
 	pub fallback_host: String,
@@ -59,10 +133,19 @@ pub struct Settings {
 	pub remote_id: Option<String>,
 	pub secure: bool,
 	pub notifications: bool,
+	pub confirm_destructive: bool,
+	pub hyperlinks: bool,
+	pub link_previews: bool,
+	pub open_downloads: bool,
+	pub multiline_input: bool,
+	pub vi_mode: bool,
+	pub chat_previews: bool,
+	pub unread_counts: bool,
 	pub authenticated: bool,
 	pub password: String,
 	pub current_chat_indicator: char,
 	pub unread_chat_indicator: char,
+	pub draft_chat_indicator: char,
 	pub chat_underline: String,
 	pub chats_title: String,
 	pub messages_title: String,
@@ -71,6 +154,18 @@ pub struct Settings {
 	pub to_title: String,
 	pub compose_title: String,
 	pub colorscheme: Colorscheme,
+	pub link_opener: String,
+	pub undo_key: String,
+	pub redo_key: String,
+	pub editor_key: String,
+	pub preview_allow: String,
+	pub preview_deny: String,
+	pub inline_images: String,
+	pub download_dir: String,
+	pub download_subdir: String,
+	pub image_height: u16,
+	pub input_max_height: u16,
+	pub history_size: usize,
 	pub poll_input: u16,
 	pub timeout: u16,
 	pub show_help: bool,
@@ -91,10 +186,19 @@ impl Settings {
 			remote_id: None,
 			secure: true,
 			notifications: true,
+			confirm_destructive: true,
+			hyperlinks: true,
+			link_previews: false,
+			open_downloads: false,
+			multiline_input: false,
+			vi_mode: false,
+			chat_previews: false,
+			unread_counts: true,
 			authenticated: false,
 			password: "toor".to_owned(),
 			current_chat_indicator: '>',
 			unread_chat_indicator: '•',
+			draft_chat_indicator: '✎',
 			chat_underline: "▔".to_owned(),
 			chats_title: "| chats |".to_owned(),
 			messages_title: "| messages |".to_owned(),
@@ -103,6 +207,18 @@ impl Settings {
 			to_title: "| to: |".to_owned(),
 			compose_title: "| message: |".to_owned(),
 			colorscheme: Colorscheme::with_name("forest", &None),
+			link_opener: "".to_owned(),
+			undo_key: "ctrl+z".to_owned(),
+			redo_key: "ctrl+shift+z".to_owned(),
+			editor_key: "ctrl+x".to_owned(),
+			preview_allow: "".to_owned(),
+			preview_deny: "".to_owned(),
+			inline_images: "off".to_owned(),
+			download_dir: "".to_owned(),
+			download_subdir: "".to_owned(),
+			image_height: 10,
+			input_max_height: 5,
+			history_size: 1000,
 			poll_input: 10,
 			timeout: 10,
 			show_help: false,
@@ -186,32 +302,7 @@ impl Settings {
 		}
 
 		while let Some(arg) = it.next() {
-			set_matches!(
-				arg,
-				("rest-host", "-u", rest_host),
-				("fallback-host", "-b", fallback_host),
-				("rest-port", "-p", rest_port),
-				("socket-host", "-o", socket_host),
-				("socket-port", "-w", socket_port),
-				("secure", "-s", secure, flag),
-				("notifications", "-n", notifications, flag),
-				("password", "-k", password),
-				("chat-indicator", "-x", current_chat_indicator),
-				("unread-indicator", "-z", unread_chat_indicator),
-				("chat-underline", "-d", chat_underline),
-				("chat-title", "-a", chats_title),
-				("messages-title", "-m", messages_title),
-				("input-title", "-y", input_title),
-				("help-title", "-e", help_title),
-				("to-title", "-q", to_title),
-				("compose-title", "-j", compose_title),
-				("poll-input", "-l", poll_input),
-				("theme", "-t", colorscheme),
-				("timeout", "-g", timeout),
-				("remote-url", "-r", remote_url, op),
-				("remote-id", "-i", remote_id, op),
-				("help", "-h", show_help, flag)
-			);
+			with_settings!(set_matches, arg, ("help", "-h", show_help, flag));
 		}
 
 		if self.socket_host.is_empty() {
@@ -230,10 +321,19 @@ impl Settings {
 						let mut parsed = Vec::new();
 
 						for i in table.keys() {
-							if let Some(value) = table[i].as_str() {
-								parsed.push(i.to_owned());
-								parsed.push(value.to_owned());
-							}
+							// the config file uses underscores in its keys,
+							// but the args use dashes
+							let key = i.replace('_', "-");
+
+							let value = match &table[i] {
+								toml::Value::String(s) => s.to_owned(),
+								toml::Value::Boolean(b) => b.to_string(),
+								toml::Value::Integer(n) => n.to_string(),
+								_ => continue,
+							};
+
+							parsed.push(key);
+							parsed.push(value);
 						}
 
 						self.parse_args(parsed, false, false);
@@ -264,11 +364,16 @@ impl Settings {
 							"hints_box",
 						];
 
+						// these were added later, so they don't have to be
+						// specified for a colorscheme to be valid
+						let optional_names =
+							["link", "chat_preview", "unread_badge", "sender_colors"];
+
 						for color_spec in arr.keys() {
 							if let Some(spec) = arr[color_spec].as_table() {
-								if spec.keys().len() != names.len() {
-									pnt!(false, "\x1b[18;1mError:\x1b[0m Your colorscheme {} does not contain the correct \
-										number of color specifiers. Please check the documentation", color_spec);
+								if names.iter().any(|n| !spec.contains_key(*n)) {
+									pnt!(false, "\x1b[18;1mError:\x1b[0m Your colorscheme {} does not contain all the required \
+										color specifiers. Please check the documentation", color_spec);
 
 									continue;
 								}
@@ -279,7 +384,9 @@ impl Settings {
 								for key in spec.keys() {
 									let mut rgb: Vec<u8> = Vec::new();
 
-									if !names.contains(&key.as_str()) {
+									if !names.contains(&key.as_str())
+										&& !optional_names.contains(&key.as_str())
+									{
 										pnt!(false, "\x1b[18;1mError:\x1b[0m You have an incorrect specification in '{}': {}", color_spec, key);
 
 										bad_spec = true;
@@ -383,3 +490,38 @@ impl Settings {
 		b
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parsed(args: &[&str]) -> Settings {
+		let mut set = Settings::default();
+		set.parse_args(args.iter().map(|a| a.to_string()).collect(), false, false);
+		set
+	}
+
+	#[test]
+	fn setting_names_are_all_completable() {
+		let mut names = SETTING_NAMES.to_vec();
+		names.sort_unstable();
+		names.dedup();
+
+		assert_eq!(names.len(), SETTING_NAMES.len());
+		assert!(SETTING_NAMES.contains(&"editor-key"));
+		assert!(!SETTING_NAMES.contains(&"help"));
+	}
+
+	#[test]
+	fn long_and_short_names_both_parse() {
+		let set = parsed(&["--link-opener", "firefox", "-N", "12", "vi-mode", "false"]);
+		assert_eq!(set.link_opener, "firefox");
+		assert_eq!(set.history_size, 12);
+		assert!(!set.vi_mode);
+
+		let set = parsed(&["-O", "firefox", "--history-size", "12", "-V"]);
+		assert_eq!(set.link_opener, "firefox");
+		assert_eq!(set.history_size, 12);
+		assert!(set.vi_mode);
+	}
+}
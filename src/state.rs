@@ -1,4 +1,7 @@
+use crate::{names::NameCache, previews::LinkPreview};
+use image::RgbaImage;
 use sdk::models::*;
+use std::collections::HashMap;
 
 #[macro_export]
 macro_rules! hint{
@@ -20,6 +23,12 @@ pub struct GlobalState {
 	pub new_text: Option<Message>,
 	pub new_chats: Option<anyhow::Result<Vec<Conversation>>>,
 	pub new_msgs: Option<anyhow::Result<Vec<Message>>>,
+	pub new_previews: Vec<(String, Option<LinkPreview>)>,
+	pub new_thumbnails: Vec<(String, Option<RgbaImage>)>,
+	// the sizes of attachments that we know, from downloading them
+	pub attachment_sizes: HashMap<String, usize>,
+	// the names of the people and chats that we've had to look up
+	pub names: NameCache,
 	pub current_chat: Option<String>,
 	pub hint_msg: String,
 	pub awaiting_new_convo: bool,
@@ -34,6 +43,10 @@ impl GlobalState {
 			new_text: None,
 			new_chats: None,
 			new_msgs: None,
+			new_previews: Vec::new(),
+			new_thumbnails: Vec::new(),
+			attachment_sizes: HashMap::new(),
+			names: NameCache::load(),
 			current_chat: None,
 			hint_msg: "type :h to get help :)".to_string(),
 			awaiting_new_convo: false,
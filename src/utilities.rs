@@ -1,19 +1,473 @@
 use crate::*;
 use chrono::prelude::*;
+use crossterm::{
+	cursor,
+	event::{KeyCode, KeyModifiers},
+	execute,
+	terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+};
 use notify_rust::Notification;
-use std::{fs::OpenOptions, io::prelude::*};
+use std::{
+	fs::{create_dir_all, read_to_string, remove_file, write, File, OpenOptions},
+	io::{prelude::*, stdout},
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+};
+use tui::layout::Rect;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 pub struct Utilities;
 
 impl Utilities {
 	pub fn date_pad_string(date: i64, width: usize) -> String {
+		let format = Utilities::format_date(date);
+
+		let pad = (width - format.len()) / 2;
+		format!("{}{}{}", " ".repeat(pad), format, " ".repeat(pad))
+	}
+
+	pub fn format_date(date: i64) -> String {
 		let unix_timestamp = (date / 1000000000) + 978307200;
 		let naive = NaiveDateTime::from_timestamp(unix_timestamp, 0);
 		let datetime: DateTime<Utc> = DateTime::from_utc(naive, Utc);
-		let format = datetime.format("%m/%d/%Y %H:%M").to_string();
+		datetime.format("%m/%d/%Y %H:%M").to_string()
+	}
 
-		let pad = (width - format.len()) / 2;
-		format!("{}{}{}", " ".repeat(pad), format, " ".repeat(pad))
+	pub fn format_size(bytes: usize) -> String {
+		// e.g. `1.2 MB`
+		let units = ["B", "KB", "MB", "GB"];
+		let mut size = bytes as f64;
+		let mut unit = 0;
+
+		while size >= 1024.0 && unit + 1 < units.len() {
+			size /= 1024.0;
+			unit += 1;
+		}
+
+		if unit == 0 {
+			format!("{} {}", bytes, units[0])
+		} else {
+			format!("{:.1} {}", size, units[unit])
+		}
+	}
+
+	pub fn parse_duration(duration: &str) -> Option<i64> {
+		// get the number of seconds in something like `30m`, `2h`, `1d`, or `1w`.
+		// Just a number is taken as minutes
+		let duration = duration.trim().to_lowercase();
+		let split = duration
+			.find(|c: char| !c.is_ascii_digit())
+			.unwrap_or(duration.len());
+
+		let (num, unit) = duration.split_at(split);
+		let num = num.parse::<i64>().ok()?;
+
+		let secs = match unit {
+			"s" | "sec" | "secs" => 1,
+			"" | "m" | "min" | "mins" => 60,
+			"h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+			"d" | "day" | "days" => 60 * 60 * 24,
+			"w" | "week" | "weeks" => 60 * 60 * 24 * 7,
+			_ => return None,
+		};
+
+		// huge numbers just aren't valid, instead of overflowing
+		num.checked_mul(secs)
+	}
+
+	pub fn normalize_address(address: &str) -> Option<String> {
+		// check that something that they typed as a recipient could be a phone
+		// number or an email, and get rid of the formatting in phone numbers
+		let address = address.trim();
+
+		if let Some((user, domain)) = address.split_once('@') {
+			let valid = !user.is_empty()
+				&& !domain.contains('@')
+				&& domain.contains('.')
+				&& !domain.starts_with('.')
+				&& !domain.ends_with('.')
+				&& !address.contains(char::is_whitespace);
+
+			return if valid {
+				Some(address.to_owned())
+			} else {
+				None
+			};
+		}
+
+		let number = address
+			.chars()
+			.filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '.'))
+			.collect::<String>();
+
+		let digits = number.strip_prefix('+').unwrap_or(&number);
+
+		// short codes (like for 2FA texts) can be as short as 3 digits
+		if (3..=15).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit()) {
+			Some(number)
+		} else {
+			None
+		}
+	}
+
+	pub fn key_matches(binding: &str, code: KeyCode, modifiers: KeyModifiers) -> bool {
+		// check if a key is the one described by a binding like `ctrl+shift+z`.
+		// Terminals disagree on whether shift makes the letter uppercase,
+		// so letters are compared without caring about case.
+		let mut parts = binding
+			.split('+')
+			.map(|p| p.trim().to_lowercase())
+			.collect::<Vec<String>>();
+
+		let key = match parts.pop() {
+			Some(key) if key.chars().count() == 1 => key.chars().next().unwrap_or_default(),
+			_ => return false,
+		};
+
+		let mut wanted = KeyModifiers::NONE;
+		for part in parts.iter() {
+			wanted |= match part.as_str() {
+				"ctrl" | "control" => KeyModifiers::CONTROL,
+				"alt" | "meta" => KeyModifiers::ALT,
+				"shift" => KeyModifiers::SHIFT,
+				_ => return false,
+			};
+		}
+
+		match code {
+			KeyCode::Char(c) => c.to_lowercase().eq(key.to_lowercase()) && modifiers == wanted,
+			_ => false,
+		}
+	}
+
+	pub fn centered_rect(width: u16, height: u16, rect: Rect) -> Rect {
+		// get a rect of the specified size in the middle of `rect`, shrinking
+		// it if it wouldn't fit, for popups to be drawn in.
+		let width = std::cmp::min(width, rect.width);
+		let height = std::cmp::min(height, rect.height);
+
+		Rect::new(
+			rect.x + (rect.width - width) / 2,
+			rect.y + (rect.height - height) / 2,
+			width,
+			height,
+		)
+	}
+
+	pub fn find_urls(text: &str) -> Vec<(usize, usize)> {
+		// get the byte range of each url in `text`. It's not perfect, but it
+		// catches pretty much everything that people actually send.
+		let mut urls = Vec::new();
+		let mut word_start = None;
+
+		// chain on a space at the end so that the last word is checked too
+		for (i, c) in text
+			.char_indices()
+			.chain(std::iter::once((text.len(), ' ')))
+		{
+			if !c.is_whitespace() {
+				if word_start.is_none() {
+					word_start = Some(i);
+				}
+				continue;
+			}
+
+			let start = match word_start.take() {
+				Some(start) => start,
+				None => continue,
+			};
+
+			// people like to put links in parentheses or quotes
+			let word = &text[start..i];
+			let trimmed = word.trim_start_matches(|c| "(<\"'".contains(c));
+			let start = start + (word.len() - trimmed.len());
+
+			let lower = trimmed.to_lowercase();
+			let prefix = ["https://", "http://", "www."]
+				.iter()
+				.find(|p| lower.starts_with(**p));
+
+			if let Some(prefix) = prefix {
+				// and they also like to end sentences right after links, so trim
+				// off punctuation (but keep closing parentheses if the link
+				// opened them itself, like wikipedia links do)
+				let mut url = trimmed;
+				while let Some(last) = url.chars().last() {
+					let unbalanced =
+						last == ')' && url.matches('(').count() < url.matches(')').count();
+
+					if ".,;:!?'\"]>".contains(last) || unbalanced {
+						url = &url[..url.len() - last.len_utf8()];
+					} else {
+						break;
+					}
+				}
+
+				if url.len() > prefix.len() {
+					urls.push((start, start + url.len()));
+				}
+			}
+		}
+
+		urls
+	}
+
+	pub fn url_target(url: &str) -> String {
+		// links like `www.example.com` need a scheme to be opened
+		if url.to_lowercase().starts_with("www.") {
+			format!("https://{}", url)
+		} else {
+			url.to_owned()
+		}
+	}
+
+	pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+		// wrap the text like `textwrap` would, but never split a url across lines,
+		// since that makes them impossible to detect (and click on). So we only
+		// break on spaces, and never in the middle of words.
+		let opts = textwrap::Options::new(width)
+			.break_words(false)
+			.word_separator(textwrap::word_separators::AsciiSpace)
+			.word_splitter(textwrap::word_splitters::NoHyphenation);
+
+		textwrap::fill(text, opts)
+			.split('\n')
+			.flat_map(|l| {
+				let is_url = Utilities::find_urls(l)
+					.iter()
+					.any(|(s, e)| e - s == l.trim().len());
+
+				if is_url || UnicodeWidthStr::width(l) <= width {
+					return vec![l.to_owned()];
+				}
+
+				// if it's just a really long word that isn't a url, break it
+				// up on grapheme boundaries like `textwrap` would have done
+				let mut lines = vec!["".to_owned()];
+				let mut line_width = 0;
+
+				for g in l.graphemes(true) {
+					let g_width = UnicodeWidthStr::width(g);
+
+					if line_width + g_width > width && line_width > 0 {
+						lines.push("".to_owned());
+						line_width = 0;
+					}
+
+					if let Some(line) = lines.last_mut() {
+						line.push_str(g);
+					}
+					line_width += g_width;
+				}
+
+				lines
+			})
+			.collect()
+	}
+
+	pub fn truncate(text: &str, width: usize) -> String {
+		// only show what part of the text will fit, with ellipsis.
+		if UnicodeWidthStr::width(text) <= width {
+			return text.to_owned();
+		}
+
+		let mut total = 0;
+		let fits = text
+			.graphemes(true)
+			.take_while(|g| {
+				total += UnicodeWidthStr::width(*g);
+				total + 3 <= width
+			})
+			.collect::<String>();
+
+		format!("{}...", fits)
+	}
+
+	pub fn open_with_system(target: &str, opener: &str) {
+		// open a file or url with whatever the user has set to open
+		// them, or the system default if they haven't set anything
+		let res = Utilities::opener_command(target, opener, std::env::consts::OS)
+			.stdin(Stdio::null())
+			.stdout(Stdio::null())
+			.stderr(Stdio::null())
+			.spawn();
+
+		match res {
+			Ok(mut child) => {
+				hint!("opened {} :)", target);
+
+				// it has to be waited on once it exits or else it sticks around
+				// as a zombie, but that could take a while (some openers run
+				// until the file is closed), so it's done on its own thread
+				std::thread::spawn(move || child.wait());
+			}
+			Err(err) => hint!("could not open {}: {}", target, err),
+		}
+	}
+
+	fn opener_command(target: &str, opener: &str, os: &str) -> Command {
+		// the target is always passed as its own argument and never through a
+		// shell, since urls come from other people's texts and things like
+		// `&` in them would be run as commands by cmd.exe on windows
+		let mut cmd = if !opener.is_empty() {
+			Command::new(opener)
+		} else if os == "macos" {
+			Command::new("open")
+		} else if os == "windows" {
+			let mut cmd = Command::new("rundll32");
+			cmd.arg("url.dll,FileProtocolHandler");
+			cmd
+		} else {
+			Command::new("xdg-open")
+		};
+
+		cmd.arg(target);
+		cmd
+	}
+
+	pub fn edit_in_editor(text: &str) -> anyhow::Result<String> {
+		// let them edit `text` in their own editor, and get what they wrote
+		let editor = std::env::var("VISUAL")
+			.or_else(|_| std::env::var("EDITOR"))
+			.unwrap_or_else(|_| {
+				if cfg!(windows) {
+					"notepad".to_owned()
+				} else {
+					"vi".to_owned()
+				}
+			});
+
+		let (file, mut handle) =
+			Utilities::create_private_file(&format!("message-{}.txt", std::process::id()))?;
+
+		let written = handle.write_all(text.as_bytes());
+		drop(handle);
+
+		// the editor needs the terminal to itself, so put it back to
+		// normal while the editor runs, and then take it back afterwards.
+		// `EDITOR` can have arguments in it too, like `code --wait`
+		let mut out = stdout();
+
+		if let Err(err) = written.and_then(|_| disable_raw_mode()) {
+			let _ = remove_file(&file);
+			return Err(err.into());
+		}
+
+		let status = execute!(
+			out,
+			Clear(ClearType::All),
+			cursor::MoveTo(0, 0),
+			cursor::Show
+		)
+		.and_then(|_| {
+			let mut parts = editor.split_whitespace();
+			let program = parts.next().ok_or_else(|| {
+				std::io::Error::new(std::io::ErrorKind::NotFound, "no editor is set")
+			})?;
+
+			// nothing gets drawn while the editor is open, but this lets the
+			// websocket keep getting texts in the background so that they
+			// show up once it's closed
+			tokio::task::block_in_place(|| Command::new(program).args(parts).arg(&file).status())
+		});
+
+		// the terminal has to be taken back no matter what happened, or
+		// else the rest of the app would be stuck in the wrong mode
+		let restored = enable_raw_mode().and_then(|_| execute!(out, Clear(ClearType::All)));
+
+		let contents = read_to_string(&file);
+		let _ = remove_file(&file);
+
+		restored?;
+		let status = status?;
+		if !status.success() {
+			return Err(anyhow::anyhow!("{} exited with {}", editor, status));
+		}
+
+		// editors like to add a newline at the end, which no one wants to send
+		Ok(contents?.trim_end_matches(['\n', '\r']).to_owned())
+	}
+
+	pub fn stable_hash(text: &str) -> u64 {
+		// FNV-1a, since the std hasher can change between versions of rust
+		text.bytes().fold(0xcbf29ce484222325_u64, |hash, b| {
+			(hash ^ b as u64).wrapping_mul(0x100000001b3)
+		})
+	}
+
+	pub fn cache_file_name(path: &str) -> String {
+		// a name to save something from the host as, that's the same every time
+		// for the same path but won't clash with others that have the same file
+		// name, e.g. `Attachments/0a/10/IMG_0001.jpeg` to `1b2c...-IMG_0001.jpeg`
+		let name = path
+			.rsplit(['/', '\\'])
+			.next()
+			.unwrap_or_default()
+			.chars()
+			.map(|c| {
+				if c.is_ascii_alphanumeric() || "._-".contains(c) {
+					c
+				} else {
+					'_'
+				}
+			})
+			.collect::<String>();
+
+		format!(
+			"{:016x}-{}",
+			Utilities::stable_hash(path),
+			name.trim_start_matches('.')
+		)
+	}
+
+	pub fn private_dir() -> std::io::Result<PathBuf> {
+		// somewhere that only they can read, for drafts and pasted images. The
+		// shared temp dir isn't good enough, since someone else on the same
+		// computer could make our directory (or a symlink to theirs) first
+		let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+			Some(runtime) if !runtime.is_empty() => {
+				let mut dir = PathBuf::from(runtime);
+				dir.push("smcurser");
+				dir
+			}
+			_ => {
+				let mut dir = cache_dir();
+				dir.push("tmp");
+				dir
+			}
+		};
+
+		create_dir_all(&dir)?;
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+		}
+
+		Ok(dir)
+	}
+
+	pub fn create_private_file(name: &str) -> std::io::Result<(PathBuf, File)> {
+		// make a new file in the private dir that only they can read or write.
+		// Anything that was left there before (like if it crashed) is replaced
+		let mut path = Utilities::private_dir()?;
+		path.push(name);
+
+		let _ = remove_file(&path);
+
+		let mut options = OpenOptions::new();
+		options.write(true).create_new(true);
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::OpenOptionsExt;
+			options.mode(0o600);
+		}
+
+		let file = options.open(&path)?;
+		Ok((path, file))
 	}
 
 	pub fn show_notification(title: &str, msg: &str) {
@@ -34,6 +488,31 @@ impl Utilities {
 			.show();
 	}
 
+	pub fn read_toml(path: &Path) -> Option<toml::value::Table> {
+		// read one of the files we store our own state in
+		let contents = read_to_string(path).ok()?;
+
+		match contents.parse::<toml::Value>() {
+			Ok(toml::Value::Table(table)) => Some(table),
+			_ => None,
+		}
+	}
+
+	pub fn write_toml(path: &Path, table: toml::value::Table) {
+		if let Some(parent) = path.parent() {
+			let _ = create_dir_all(parent);
+		}
+
+		match toml::to_string(&toml::Value::Table(table)) {
+			Ok(contents) => {
+				if let Err(err) = write(path, contents) {
+					hint!("could not save {}: {}", path.display(), err);
+				}
+			}
+			Err(err) => hint!("could not save {}: {}", path.display(), err),
+		}
+	}
+
 	#[allow(dead_code)]
 	pub fn log(log_str: String) {
 		let mut file = OpenOptions::new()
@@ -55,3 +534,175 @@ impl Utilities {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn opener_never_uses_a_shell() {
+		let url = "https://x.com/&calc|whoami^%PATH%";
+
+		for os in ["windows", "macos", "linux"] {
+			let cmd = Utilities::opener_command(url, "", os);
+			let program = cmd.get_program().to_string_lossy().to_lowercase();
+			let args = cmd.get_args().collect::<Vec<_>>();
+
+			assert!(!["cmd", "cmd.exe", "sh", "bash"].contains(&program.as_str()));
+			assert_eq!(args.last().unwrap().to_string_lossy(), url);
+		}
+
+		let cmd = Utilities::opener_command(url, "", "windows");
+		assert_eq!(cmd.get_program(), "rundll32");
+		assert_eq!(cmd.get_args().count(), 2);
+
+		// and what they set is used as-is, with the url as one argument
+		let cmd = Utilities::opener_command(url, "firefox", "windows");
+		assert_eq!(cmd.get_program(), "firefox");
+		assert_eq!(cmd.get_args().collect::<Vec<_>>(), [url]);
+	}
+
+	#[test]
+	fn cache_file_names_are_stable_and_safe() {
+		// this is what FNV-1a gives for these, so the names don't change
+		// when rust does and the cache doesn't get thrown away
+		assert_eq!(Utilities::stable_hash(""), 0xcbf29ce484222325);
+		assert_eq!(Utilities::stable_hash("a"), 0xaf63dc4c8601ec8c);
+
+		let name = Utilities::cache_file_name("Attachments/0a/10/IMG 0001.jpeg");
+		assert!(name.ends_with("-IMG_0001.jpeg"));
+		assert_eq!(
+			name,
+			Utilities::cache_file_name("Attachments/0a/10/IMG 0001.jpeg")
+		);
+
+		// the same file name in a different place is a different file
+		assert_ne!(
+			name,
+			Utilities::cache_file_name("Attachments/0b/11/IMG 0001.jpeg")
+		);
+
+		// and nothing can get out of the cache directory
+		for path in ["../../.bashrc", "a/..", "/", "a\\..\\b"] {
+			let name = Utilities::cache_file_name(path);
+			assert!(!name.contains(['/', '\\']));
+			assert!(!name.ends_with(".."));
+			assert!(!name.starts_with('.'));
+		}
+	}
+
+	#[test]
+	fn normalize_address_accepts_phone_numbers() {
+		let norm = Utilities::normalize_address;
+
+		assert_eq!(norm("+1 (123) 123-1234"), Some("+11231231234".to_owned()));
+		assert_eq!(norm(" 123.123.1234 "), Some("1231231234".to_owned()));
+		assert_eq!(norm("12345"), Some("12345".to_owned()));
+
+		assert_eq!(norm("12"), None);
+		assert_eq!(norm("1234567890123456"), None);
+		assert_eq!(norm("+"), None);
+		assert_eq!(norm("1+23"), None);
+		assert_eq!(norm("123abc"), None);
+		assert_eq!(norm(""), None);
+	}
+
+	#[test]
+	fn normalize_address_accepts_emails() {
+		let norm = Utilities::normalize_address;
+
+		assert_eq!(norm("me@example.com"), Some("me@example.com".to_owned()));
+		assert_eq!(
+			norm(" a.b+c@mail.co.uk "),
+			Some("a.b+c@mail.co.uk".to_owned())
+		);
+
+		assert_eq!(norm("@example.com"), None);
+		assert_eq!(norm("me@example"), None);
+		assert_eq!(norm("me@.com"), None);
+		assert_eq!(norm("me@example."), None);
+		assert_eq!(norm("me@a@example.com"), None);
+		assert_eq!(norm("my name@example.com"), None);
+	}
+
+	#[test]
+	fn parse_duration_units() {
+		assert_eq!(Utilities::parse_duration("30"), Some(30 * 60));
+		assert_eq!(Utilities::parse_duration("45s"), Some(45));
+		assert_eq!(Utilities::parse_duration(" 2H "), Some(2 * 60 * 60));
+		assert_eq!(Utilities::parse_duration("1day"), Some(60 * 60 * 24));
+		assert_eq!(Utilities::parse_duration("1w"), Some(60 * 60 * 24 * 7));
+
+		assert_eq!(Utilities::parse_duration("w"), None);
+		assert_eq!(Utilities::parse_duration("3y"), None);
+		assert_eq!(Utilities::parse_duration("-3m"), None);
+	}
+
+	#[test]
+	fn parse_duration_does_not_overflow() {
+		assert_eq!(Utilities::parse_duration("9999999999999999w"), None);
+		assert_eq!(Utilities::parse_duration("99999999999999999999"), None);
+	}
+
+	#[test]
+	fn key_matches_modifiers() {
+		let ctrl = KeyModifiers::CONTROL;
+		let ctrl_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+
+		assert!(Utilities::key_matches("ctrl+z", KeyCode::Char('z'), ctrl));
+		assert!(Utilities::key_matches(
+			" Control + Z ",
+			KeyCode::Char('z'),
+			ctrl
+		));
+		assert!(Utilities::key_matches(
+			"alt+shift+u",
+			KeyCode::Char('U'),
+			KeyModifiers::ALT | KeyModifiers::SHIFT
+		));
+
+		// the modifiers have to be exactly the same
+		assert!(!Utilities::key_matches(
+			"ctrl+z",
+			KeyCode::Char('z'),
+			KeyModifiers::NONE
+		));
+		assert!(!Utilities::key_matches(
+			"ctrl+z",
+			KeyCode::Char('z'),
+			ctrl_shift
+		));
+		assert!(!Utilities::key_matches(
+			"ctrl+shift+z",
+			KeyCode::Char('z'),
+			ctrl
+		));
+	}
+
+	#[test]
+	fn key_matches_ignores_letter_case() {
+		let ctrl_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+
+		assert!(Utilities::key_matches(
+			"ctrl+shift+z",
+			KeyCode::Char('Z'),
+			ctrl_shift
+		));
+		assert!(Utilities::key_matches(
+			"ctrl+shift+z",
+			KeyCode::Char('z'),
+			ctrl_shift
+		));
+	}
+
+	#[test]
+	fn key_matches_rejects_bad_bindings() {
+		let ctrl = KeyModifiers::CONTROL;
+
+		assert!(!Utilities::key_matches("", KeyCode::Char('z'), ctrl));
+		assert!(!Utilities::key_matches("ctrl+", KeyCode::Char('z'), ctrl));
+		assert!(!Utilities::key_matches("ctrl+zz", KeyCode::Char('z'), ctrl));
+		assert!(!Utilities::key_matches("super+z", KeyCode::Char('z'), ctrl));
+		assert!(!Utilities::key_matches("ctrl+z", KeyCode::Enter, ctrl));
+	}
+}